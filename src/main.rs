@@ -1,15 +1,41 @@
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
-use dirs::cache_dir;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use clap::{Parser, Subcommand, ValueEnum};
+use dirs::{cache_dir, config_dir, home_dir};
+use fastembed::{
+    EmbeddingModel, InitOptions, InitOptionsUserDefined, Pooling, QuantizationMode, TextEmbedding,
+    TokenizerFiles, UserDefinedEmbeddingModel,
+};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue, LINK, USER_AGENT};
-use rusqlite::{Connection, ffi::sqlite3_auto_extension, params};
+use reqwest::StatusCode;
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CONTENT_LENGTH, HeaderMap, HeaderName, HeaderValue, LINK, USER_AGENT,
+};
+use rusqlite::backup::Progress;
+use rusqlite::{
+    Connection, MAIN_DB, OpenFlags, TransactionBehavior, ffi::sqlite3_auto_extension, params,
+};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlite_vec::sqlite3_vec_init;
+use std::collections::{HashMap, HashSet};
 use std::fs::create_dir_all;
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::io::Write;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Parser)]
 #[command(
@@ -18,10 +44,26 @@ use std::time::SystemTime;
     version
 )]
 struct Cli {
+    /// Path to the SQLite database (overrides the profile and default location)
+    #[arg(long, global = true, env = "GH_STARS_DB")]
+    db: Option<PathBuf>,
+
+    /// Named profile from the config file to use
+    #[arg(long, global = true, env = "GH_STARS_PROFILE")]
+    profile: Option<String>,
+
+    /// Keyword-only mode: store repositories without vectors and search by keyword only
+    #[arg(long, global = true)]
+    no_embed: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+// A comma separated list parsed as one value. The alias keeps clap from treating
+// the field as a repeated argument, which would not match the value parsers.
+type CommaSeparated = Vec<String>;
+
 #[derive(Subcommand)]
 enum Commands {
     /// Fetch and cache stars for a GitHub user
@@ -41,35 +83,226 @@ enum Commands {
     Search {
         /// GitHub username(s) whose stars to search (comma separated)
         #[arg(short, long, value_parser = parse_usernames)]
-        username: Option<Vec<String>>,
+        username: Option<CommaSeparated>,
 
         /// Programming language(s) to filter by (comma separated)
         #[arg(long, value_parser = parse_languages)]
-        language: Option<Vec<String>>,
+        language: Option<CommaSeparated>,
 
-        /// Search terms (searches across name, description, and other fields)
-        #[arg(trailing_var_arg = true)]
+        /// Search terms, with qualifiers like lang:rust stars:>500 owner:tokio-rs -archived
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         terms: Vec<String>,
 
-        /// Maximum number of results to return
-        #[arg(short, long, default_value = "30")]
-        limit: usize,
+        /// Maximum number of results to return [default: 30]
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Rank by keyword matches, semantic similarity or both [default: hybrid]
+        #[arg(short, long)]
+        mode: Option<SearchMode>,
+
+        /// Share of semantic similarity in hybrid ranking, from 0 to 1 [default: 0.5]
+        #[arg(short = 'w', long, value_parser = parse_weight)]
+        semantic_weight: Option<f64>,
     },
     /// List all cached stars for a user
     List {
         /// GitHub username(s) whose stars to list (comma separated)
         #[arg(short, long, value_parser = parse_usernames)]
-        username: Option<Vec<String>>,
+        username: Option<CommaSeparated>,
 
-        /// Maximum number of results to return
-        #[arg(short, long, default_value = "30")]
-        limit: usize,
+        /// Maximum number of results to return [default: 30]
+        #[arg(short, long)]
+        limit: Option<usize>,
     },
     /// Show detailed information about a specific repository
     Info {
         /// Repository in format user/repo
         repo: String,
     },
+    /// Unstar cached repositories that match all of the given rules
+    Prune {
+        /// GitHub username whose stars to prune (must own the token)
+        username: String,
+
+        /// Select archived repositories
+        #[arg(long)]
+        archived: bool,
+
+        /// Select repositories with no push in the last N years
+        #[arg(long, value_name = "N")]
+        stale_years: Option<u64>,
+
+        /// Select forked repositories
+        #[arg(long)]
+        forks: bool,
+
+        /// Programming language(s) to select (comma separated)
+        #[arg(long, value_parser = parse_languages)]
+        language: Option<CommaSeparated>,
+
+        /// Select repositories matching this keyword search query
+        #[arg(short, long)]
+        query: Option<String>,
+
+        /// Only show the repositories that would be unstarred
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// GitHub API token (overrides GITHUB_TOKEN env var)
+        #[arg(short, long)]
+        token: Option<String>,
+    },
+    /// Star the repositories listed in a prune log again
+    Restar {
+        /// Log file written by the prune command
+        log: PathBuf,
+
+        /// GitHub API token (overrides GITHUB_TOKEN env var)
+        #[arg(short, long)]
+        token: Option<String>,
+    },
+    /// Save a consistent snapshot of the database
+    Backup {
+        /// File to write the snapshot to
+        file: PathBuf,
+
+        /// Compress the snapshot with gzip (implied by a .gz extension)
+        #[arg(short = 'z', long)]
+        compress: bool,
+    },
+    /// Replace the database with a snapshot written by backup
+    Restore {
+        /// Snapshot to restore, compressed or not
+        file: PathBuf,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show, set or clear a personal note on a cached repository
+    Note {
+        /// Repository in format user/repo
+        repo: String,
+
+        /// Note text, shown by info and embedded through {notes} in the embedding template
+        #[arg(trailing_var_arg = true)]
+        text: Vec<String>,
+
+        /// Remove the note
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+    /// Regenerate embeddings from the cached repositories without fetching from GitHub
+    Reembed {
+        /// Only re-embed the stars of these users (comma separated)
+        #[arg(short, long, value_parser = parse_usernames)]
+        user: Option<CommaSeparated>,
+
+        /// Embedding model to switch to (fastembed model code or variant name, or ollama:<model> / openai:<model>)
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Also re-embed repositories whose vector is up to date
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Rebuild the repository columns and search index from stored GitHub data, without network access
+    Reindex,
+    /// Remove a user and the repositories only they starred from the cache
+    Forget {
+        /// GitHub username to remove
+        username: String,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show statistics about the cache
+    Stats {
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Number of top languages and owners to show
+        #[arg(long, default_value = "10")]
+        top: usize,
+    },
+    /// Check, repair or compact the database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Download or package the embedding model for offline use
+    Model {
+        #[command(subcommand)]
+        command: ModelCommands,
+    },
+    /// Keep the embedding model loaded in a background process to speed up searches
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Start the daemon in the background
+    Start,
+    /// Run the daemon in the foreground, e.g. from a service manager
+    Run,
+    /// Stop the running daemon
+    Stop,
+    /// Show whether the daemon is running and which model it serves
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ModelCommands {
+    /// Download the embedding model into the model cache
+    Prefetch {
+        /// fastembed model to download instead of the configured one
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// Copy the embedding model into a directory usable as embedding_model_path
+    Export {
+        /// Directory to write the model files to
+        dir: PathBuf,
+
+        /// fastembed model to export instead of the configured one
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Run integrity checks and look for orphaned rows and missing vectors
+    Check,
+    /// Remove orphaned rows, rebuild the full-text index and embed repos without a vector
+    Repair,
+    /// Compact the database file and report the space reclaimed
+    Vacuum,
+    /// Store the vectors as float, int8 or binary without re-embedding
+    Quantize {
+        /// How to store the vectors
+        storage: VectorStorage,
+    },
+    /// Compare the recall and latency of quantized vectors against float
+    Benchmark {
+        /// Number of stored vectors to use as queries
+        #[arg(short, long, default_value = "100")]
+        queries: usize,
+
+        /// Number of nearest neighbours to compare
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -92,6 +325,57 @@ struct StarredRepo {
     #[serde(rename = "updated_at")]
     updated_at: String,
     created_at: Option<String>,
+    #[serde(default)]
+    pushed_at: Option<String>,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    fork: bool,
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    homepage: Option<String>,
+    // Cached users who starred the repo, filled in from user_stars when read back
+    #[serde(skip)]
+    starred_by: Vec<String>,
+    // Personal note, filled in from repo_notes when read back
+    #[serde(skip)]
+    notes: Option<String>,
+}
+
+// An item of the starred list when requested with the star+json media type
+#[derive(Debug, Deserialize)]
+struct Star {
+    starred_at: Option<String>,
+    repo: StarredRepo,
+    // The repo object exactly as GitHub returned it, including fields StarredRepo drops
+    #[serde(skip)]
+    payload: serde_json::Value,
+}
+
+// Subquery listing every cached user who starred the repo aliased as `r`
+const STARRED_BY_SQL: &str =
+    "(SELECT group_concat(s.username, ',') FROM user_stars s WHERE s.repo_id = r.id) AS starred_by";
+
+// Subquery reading the personal note of the repo aliased as `r`
+const NOTES_SQL: &str = "(SELECT n.note FROM repo_notes n WHERE n.repo_id = r.id) AS notes";
+
+// Read a repo from a row with a `json` column and optionally `starred_by` and `notes` columns
+fn repo_from_row(row: &rusqlite::Row) -> rusqlite::Result<StarredRepo> {
+    let json: String = row.get("json")?;
+    let mut repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+
+    if let Ok(Some(starred_by)) = row.get::<_, Option<String>>("starred_by") {
+        repo.starred_by = starred_by.split(',').map(str::to_string).collect();
+    }
+
+    if let Ok(notes) = row.get::<_, Option<String>>("notes") {
+        repo.notes = notes;
+    }
+
+    Ok(repo)
 }
 
 // Get the cache directory path for the application
@@ -102,37 +386,270 @@ fn get_cache_dir() -> Result<PathBuf> {
     Ok(path)
 }
 
-// Get the path to the SQLite database (one DB for all users)
+// Settings that can be shared by every profile or set per profile in the config file
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct Profile {
+    // Database location, defaults to the cache directory
+    db: Option<PathBuf>,
+    // Environment variable holding the GitHub token
+    token_env: Option<String>,
+    // Shell command printing the GitHub token
+    token_command: Option<String>,
+    // Users to search and list when --username isn't given
+    users: Option<Vec<String>>,
+    // Default result limit for search and list
+    limit: Option<usize>,
+    // Number of texts embedded per batch
+    embed_batch_size: Option<usize>,
+    // Where embeddings are computed: fastembed (default), openai or ollama
+    embedding_backend: Option<String>,
+    // fastembed model code, e.g. Xenova/bge-small-en-v1.5, or the server's model name
+    embedding_model: Option<String>,
+    // Base URL of the embedding server
+    embedding_url: Option<String>,
+    // Environment variable holding the embedding server's API key
+    embedding_api_key_env: Option<String>,
+    // Header the API key is sent in, instead of a bearer token
+    embedding_auth_header: Option<String>,
+    // Vector length of the server's or local model, probed when unset
+    embedding_dimension: Option<usize>,
+    // Ask an OpenAI API server for vectors of embedding_dimension
+    request_dimensions: Option<bool>,
+    // Directory with a local ONNX model and its tokenizer, used instead of a fastembed download
+    embedding_model_path: Option<PathBuf>,
+    // Where fastembed models are downloaded to, defaults to models/ in the cache directory
+    model_cache_dir: Option<PathBuf>,
+    // Text embedded for each repo, with {field} placeholders
+    embedding_template: Option<String>,
+    // Embed repos and queries, false for keyword-only fetch and search
+    semantic: Option<bool>,
+    // Query embeddings kept in the database, 0 to disable the cache
+    query_cache_size: Option<usize>,
+    // Default search ranking: keyword, semantic or hybrid
+    search_mode: Option<String>,
+    // Share of semantic similarity in hybrid ranking, from 0 to 1
+    semantic_weight: Option<f64>,
+}
+
+impl Profile {
+    // Settings of `self`, falling back to `base` for anything unset
+    fn or(&self, base: &Profile) -> Profile {
+        Profile {
+            db: self.db.clone().or_else(|| base.db.clone()),
+            token_env: self.token_env.clone().or_else(|| base.token_env.clone()),
+            token_command: self
+                .token_command
+                .clone()
+                .or_else(|| base.token_command.clone()),
+            users: self.users.clone().or_else(|| base.users.clone()),
+            limit: self.limit.or(base.limit),
+            embed_batch_size: self.embed_batch_size.or(base.embed_batch_size),
+            embedding_backend: self
+                .embedding_backend
+                .clone()
+                .or_else(|| base.embedding_backend.clone()),
+            embedding_model: self
+                .embedding_model
+                .clone()
+                .or_else(|| base.embedding_model.clone()),
+            embedding_url: self
+                .embedding_url
+                .clone()
+                .or_else(|| base.embedding_url.clone()),
+            embedding_api_key_env: self
+                .embedding_api_key_env
+                .clone()
+                .or_else(|| base.embedding_api_key_env.clone()),
+            embedding_auth_header: self
+                .embedding_auth_header
+                .clone()
+                .or_else(|| base.embedding_auth_header.clone()),
+            embedding_dimension: self.embedding_dimension.or(base.embedding_dimension),
+            request_dimensions: self.request_dimensions.or(base.request_dimensions),
+            embedding_model_path: self
+                .embedding_model_path
+                .clone()
+                .or_else(|| base.embedding_model_path.clone()),
+            model_cache_dir: self
+                .model_cache_dir
+                .clone()
+                .or_else(|| base.model_cache_dir.clone()),
+            embedding_template: self
+                .embedding_template
+                .clone()
+                .or_else(|| base.embedding_template.clone()),
+            semantic: self.semantic.or(base.semantic),
+            query_cache_size: self.query_cache_size.or(base.query_cache_size),
+            search_mode: self
+                .search_mode
+                .clone()
+                .or_else(|| base.search_mode.clone()),
+            semantic_weight: self.semantic_weight.or(base.semantic_weight),
+        }
+    }
+}
+
+// Contents of config.toml. Top-level settings apply to every profile and
+// `[profiles.<name>]` tables override them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    // Profile used when neither --profile nor GH_STARS_PROFILE is set
+    default_profile: Option<String>,
+    #[serde(flatten)]
+    base: Profile,
+    profiles: HashMap<String, Profile>,
+}
+
+// Settings resolved once at startup from the command line, environment and config file
+#[derive(Debug, Default)]
+struct Settings {
+    db_path: Option<PathBuf>,
+    profile_name: Option<String>,
+    profile: Profile,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+// Whether repos and queries are embedded, off with --no-embed or `semantic = false`
+fn semantic_enabled() -> bool {
+    settings().profile.semantic.unwrap_or(true)
+}
+
+// Get the path of the config file
+fn get_config_path() -> Result<PathBuf> {
+    if let Ok(path) = std::env::var("GH_STARS_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+
+    let mut path = config_dir().ok_or_else(|| anyhow!("Failed to determine config directory"))?;
+    path.push("gh-stars");
+    path.push("config.toml");
+    Ok(path)
+}
+
+fn load_config() -> Result<Config> {
+    let path = get_config_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config file {}", path.display()))
+}
+
+// Expand a leading `~` to the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+// Resolve the active profile and database location, must run before the database is opened
+fn init_settings(
+    cli_db: Option<PathBuf>,
+    cli_profile: Option<String>,
+    no_embed: bool,
+) -> Result<()> {
+    let config = load_config()?;
+
+    let profile_name = cli_profile.or(config.default_profile);
+    let profile = match &profile_name {
+        Some(name) => config
+            .profiles
+            .get(name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Profile '{}' not found in config file {}",
+                    name,
+                    get_config_path()
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_default()
+                )
+            })?
+            .or(&config.base),
+        None => config.base,
+    };
+    let profile = Profile {
+        semantic: if no_embed {
+            Some(false)
+        } else {
+            profile.semantic
+        },
+        ..profile
+    };
+
+    let db_path = cli_db
+        .or_else(|| profile.db.clone())
+        .map(|path| expand_home(&path));
+
+    SETTINGS
+        .set(Settings {
+            db_path,
+            profile_name,
+            profile,
+        })
+        .map_err(|_| anyhow!("Settings were already initialized"))
+}
+
+// Get the path to the SQLite database (one DB for all users of a profile)
 fn get_db_path() -> Result<PathBuf> {
+    let settings = settings();
+
+    if let Some(db_path) = &settings.db_path {
+        if let Some(parent) = db_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            create_dir_all(parent)?;
+        }
+        return Ok(db_path.clone());
+    }
+
+    // Profiles without an explicit database get their own file in the cache directory
     let mut db_path = get_cache_dir()?;
-    db_path.push("stars.db");
+    match &settings.profile_name {
+        Some(name) => db_path.push(format!("stars-{}.db", name)),
+        None => db_path.push("stars.db"),
+    }
     Ok(db_path)
 }
 
 fn u64_to_sqlite(value: u64, field: &str) -> Result<i64> {
-    i64::try_from(value).map_err(|_| anyhow!("{} value {} exceeds SQLite INTEGER range", field, value))
+    i64::try_from(value)
+        .map_err(|_| anyhow!("{} value {} exceeds SQLite INTEGER range", field, value))
 }
 
-fn optional_u64_to_sqlite(value: Option<u64>, field: &str) -> Result<Option<i64>> {
-    value.map(|value| u64_to_sqlite(value, field)).transpose()
+// A schema change. Migrations run in order and `PRAGMA user_version` records
+// how many of them have been applied to a database.
+struct Migration {
+    description: &'static str,
+    sql: &'static str,
+    // Rewrites or drops existing data, so the database is backed up first
+    destructive: bool,
 }
 
-// Initialize SQLite database with vector extension
-fn init_db() -> Result<Connection> {
-    let db_path = get_db_path()?;
-    let conn = Connection::open(&db_path)?;
-
-    // Create tables if they don't exist
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (
+// Never edit or reorder released migrations, only append new ones
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "create users, repos and repo_vectors tables",
+        // IF NOT EXISTS adopts databases created before schema versioning
+        sql: "CREATE TABLE IF NOT EXISTS users (
             username TEXT PRIMARY KEY,
             last_updated INTEGER NOT NULL
-        )",
-        [],
-    )?;
+        );
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS repos (
+        CREATE TABLE IF NOT EXISTS repos (
             id INTEGER,
             username TEXT NOT NULL,
             full_name TEXT NOT NULL,
@@ -149,17 +666,303 @@ fn init_db() -> Result<Connection> {
             json TEXT NOT NULL,
             PRIMARY KEY (id, username),
             FOREIGN KEY (username) REFERENCES users(username)
-        )",
-        [],
-    )?;
+        );
 
-    // Updated to use vec0 virtual table
-    conn.execute(
-        "CREATE VIRTUAL TABLE IF NOT EXISTS repo_vectors USING vec0(
+        CREATE VIRTUAL TABLE IF NOT EXISTS repo_vectors USING vec0(
             embedding float[384]
-        )",
-        [],
-    )?;
+        );",
+        destructive: false,
+    },
+    Migration {
+        description: "share repos between users through user_stars",
+        sql: "CREATE TABLE repos_shared (
+            id INTEGER PRIMARY KEY,
+            full_name TEXT NOT NULL,
+            name TEXT NOT NULL,
+            owner TEXT NOT NULL,
+            html_url TEXT NOT NULL,
+            description TEXT,
+            language TEXT,
+            stars INTEGER NOT NULL,
+            forks INTEGER,
+            open_issues INTEGER,
+            updated_at TEXT NOT NULL,
+            created_at TEXT,
+            json TEXT NOT NULL
+        );
+
+        INSERT INTO repos_shared
+        SELECT id, full_name, name, owner, html_url, description, language, stars,
+            forks, open_issues, updated_at, created_at, json
+        FROM repos
+        GROUP BY id;
+
+        -- Renaming repos_shared below rewrites the foreign key to reference repos
+        CREATE TABLE user_stars (
+            username TEXT NOT NULL,
+            repo_id INTEGER NOT NULL,
+            starred_at TEXT,
+            PRIMARY KEY (username, repo_id),
+            FOREIGN KEY (username) REFERENCES users(username),
+            FOREIGN KEY (repo_id) REFERENCES repos_shared(id)
+        );
+
+        INSERT INTO user_stars (username, repo_id)
+        SELECT username, id FROM repos;
+
+        DROP TABLE repos;
+        ALTER TABLE repos_shared RENAME TO repos;
+
+        CREATE INDEX user_stars_repo_id ON user_stars(repo_id);",
+        destructive: true,
+    },
+    Migration {
+        description: "add topics and a full-text index over repos",
+        sql: "ALTER TABLE repos ADD COLUMN topics TEXT;
+
+        UPDATE repos SET topics = (
+            SELECT group_concat(value, ' ') FROM json_each(repos.json, '$.topics')
+        );
+
+        CREATE VIRTUAL TABLE repos_fts USING fts5(
+            name, full_name, description, topics, owner,
+            content = 'repos',
+            content_rowid = 'id',
+            tokenize = 'porter unicode61'
+        );
+
+        INSERT INTO repos_fts(repos_fts) VALUES ('rebuild');
+
+        CREATE TRIGGER repos_fts_insert AFTER INSERT ON repos BEGIN
+            INSERT INTO repos_fts(rowid, name, full_name, description, topics, owner)
+            VALUES (new.id, new.name, new.full_name, new.description, new.topics, new.owner);
+        END;
+
+        CREATE TRIGGER repos_fts_delete AFTER DELETE ON repos BEGIN
+            INSERT INTO repos_fts(repos_fts, rowid, name, full_name, description, topics, owner)
+            VALUES ('delete', old.id, old.name, old.full_name, old.description, old.topics, old.owner);
+        END;
+
+        CREATE TRIGGER repos_fts_update AFTER UPDATE ON repos BEGIN
+            INSERT INTO repos_fts(repos_fts, rowid, name, full_name, description, topics, owner)
+            VALUES ('delete', old.id, old.name, old.full_name, old.description, old.topics, old.owner);
+            INSERT INTO repos_fts(rowid, name, full_name, description, topics, owner)
+            VALUES (new.id, new.name, new.full_name, new.description, new.topics, new.owner);
+        END;",
+        destructive: false,
+    },
+    Migration {
+        description: "add homepage, pushed_at, archived and fork columns",
+        sql: "ALTER TABLE repos ADD COLUMN homepage TEXT;
+        ALTER TABLE repos ADD COLUMN pushed_at TEXT;
+        ALTER TABLE repos ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE repos ADD COLUMN fork INTEGER NOT NULL DEFAULT 0;
+
+        UPDATE repos SET
+            homepage = json_extract(json, '$.homepage'),
+            pushed_at = json_extract(json, '$.pushed_at'),
+            archived = COALESCE(json_extract(json, '$.archived'), 0),
+            fork = COALESCE(json_extract(json, '$.fork'), 0);",
+        destructive: false,
+    },
+    Migration {
+        description: "record the embedding model of repo_vectors",
+        // Existing vectors were all made with the model gh-stars used to hard-code
+        sql: "CREATE TABLE meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        INSERT INTO meta (key, value) VALUES
+            ('embedding_model', 'Qdrant/all-MiniLM-L6-v2-onnx'),
+            ('embedding_dimension', '384');",
+        destructive: false,
+    },
+    Migration {
+        description: "add README excerpts and personal notes for embedding",
+        sql: "CREATE TABLE repo_readmes (
+            repo_id INTEGER PRIMARY KEY,
+            excerpt TEXT NOT NULL,
+            fetched_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE repo_notes (
+            repo_id INTEGER PRIMARY KEY,
+            note TEXT NOT NULL
+        );
+
+        CREATE TRIGGER repos_extras_delete AFTER DELETE ON repos BEGIN
+            DELETE FROM repo_readmes WHERE repo_id = old.id;
+            DELETE FROM repo_notes WHERE repo_id = old.id;
+        END;
+
+        INSERT INTO meta (key, value) VALUES
+            ('embedding_template', '{name} {language} {description}');",
+        destructive: false,
+    },
+    Migration {
+        description: "record a hash of the text each vector was embedded from",
+        sql: "CREATE TABLE repo_vector_hashes (
+            repo_id INTEGER PRIMARY KEY,
+            hash TEXT NOT NULL
+        );
+
+        CREATE TRIGGER repos_vector_hashes_delete AFTER DELETE ON repos BEGIN
+            DELETE FROM repo_vector_hashes WHERE repo_id = old.id;
+        END;",
+        destructive: false,
+    },
+    Migration {
+        description: "cache query embeddings",
+        sql: "CREATE TABLE query_embeddings (
+            model TEXT NOT NULL,
+            query TEXT NOT NULL,
+            embedding BLOB NOT NULL,
+            last_used INTEGER NOT NULL,
+            PRIMARY KEY (model, query)
+        );
+
+        CREATE INDEX query_embeddings_last_used ON query_embeddings(last_used);",
+        destructive: false,
+    },
+];
+
+// Typed columns of repos and how each is derived from the GitHub payload in
+// the `json` column. Must list every column added by the migrations. Inside
+// json_each the payload has to be qualified as `repos.json`, since the table
+// valued function has a `json` column of its own.
+const DERIVED_COLUMNS: &[(&str, &str)] = &[
+    ("full_name", "json_extract(json, '$.full_name')"),
+    ("name", "json_extract(json, '$.name')"),
+    ("owner", "json_extract(json, '$.owner.login')"),
+    ("html_url", "json_extract(json, '$.html_url')"),
+    ("description", "json_extract(json, '$.description')"),
+    ("language", "json_extract(json, '$.language')"),
+    ("stars", "json_extract(json, '$.stargazers_count')"),
+    ("forks", "json_extract(json, '$.forks_count')"),
+    ("open_issues", "json_extract(json, '$.open_issues_count')"),
+    ("updated_at", "json_extract(json, '$.updated_at')"),
+    ("created_at", "json_extract(json, '$.created_at')"),
+    (
+        "topics",
+        "(SELECT group_concat(value, ' ') FROM json_each(repos.json, '$.topics'))",
+    ),
+    ("homepage", "json_extract(json, '$.homepage')"),
+    ("pushed_at", "json_extract(json, '$.pushed_at')"),
+    ("archived", "COALESCE(json_extract(json, '$.archived'), 0)"),
+    ("fork", "COALESCE(json_extract(json, '$.fork'), 0)"),
+];
+
+// Insert or refresh a repo from its id and GitHub payload, deriving the typed columns
+fn upsert_repo_sql() -> String {
+    let columns: Vec<&str> = DERIVED_COLUMNS.iter().map(|(column, _)| *column).collect();
+    let expressions: Vec<&str> = DERIVED_COLUMNS.iter().map(|(_, expr)| *expr).collect();
+    let updates: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect();
+
+    // The parameters are aliased as `repos` so the expressions read them like a
+    // row of the table. `WHERE true` keeps SQLite from parsing ON CONFLICT as a
+    // join constraint.
+    format!(
+        "INSERT INTO repos (id, json, {})
+        SELECT id, json, {} FROM (SELECT ? AS id, ? AS json) AS repos WHERE true
+        ON CONFLICT(id) DO UPDATE SET json = excluded.json, {}",
+        columns.join(", "),
+        expressions.join(", "),
+        updates.join(", ")
+    )
+}
+
+fn schema_version(conn: &Connection) -> Result<usize> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(usize::try_from(version).unwrap_or(0))
+}
+
+// Bring the database schema up to date with this binary
+fn migrate(conn: &mut Connection, db_path: &Path) -> Result<()> {
+    let latest = MIGRATIONS.len();
+    let current = schema_version(conn)?;
+
+    if current > latest {
+        return Err(anyhow!(
+            "Database {} has schema version {}, but this version of gh-stars only supports up to {}. Upgrade gh-stars to use it.",
+            db_path.display(),
+            current,
+            latest
+        ));
+    }
+
+    if current == latest {
+        return Ok(());
+    }
+
+    // Version 0 is either a new, empty file or a database from before
+    // versioning, whose data the destructive migrations rewrite too
+    let has_data = current > 0
+        || count(
+            conn,
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'repos'",
+        )? > 0;
+    if has_data && MIGRATIONS[current..].iter().any(|m| m.destructive) {
+        let backup_path = db_path.with_extension(format!("v{}.bak", current));
+        if backup_path.exists() {
+            std::fs::remove_file(&backup_path)?;
+        }
+        conn.execute(
+            "VACUUM INTO ?",
+            params![backup_path.to_string_lossy().into_owned()],
+        )
+        .with_context(|| format!("Failed to back up database to {}", backup_path.display()))?;
+        println!("Backed up database to {}", backup_path.display());
+    }
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    // Another process may have migrated while we waited for the write lock
+    let current = schema_version(&tx)?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        tx.execute_batch(migration.sql).with_context(|| {
+            format!(
+                "Failed to apply database migration {} ({})",
+                index + 1,
+                migration.description
+            )
+        })?;
+    }
+
+    tx.pragma_update(None, "user_version", latest as i64)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+// How long to wait for a lock held by another process
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Open the SQLite database with vector extension and apply pending migrations
+fn init_db() -> Result<Connection> {
+    let db_path = get_db_path()?;
+    let mut conn = Connection::open(&db_path)?;
+
+    // Wait for other gh-stars processes instead of failing with "database is locked"
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    // WAL lets searches read while a fetch writes, and the setting is stored in the file
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        eprintln!(
+            "Warning: {} doesn't support WAL mode (using {}), concurrent access may fail",
+            db_path.display(),
+            journal_mode
+        );
+    }
+    // Safe with WAL and avoids an fsync on every commit
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+    migrate(&mut conn, &db_path)?;
 
     Ok(conn)
 }
@@ -172,62 +975,277 @@ fn has_next_page(headers: &HeaderMap) -> bool {
         .unwrap_or(false)
 }
 
-fn get_github_token(cli_token: &Option<String>) -> Option<String> {
+fn get_github_token(cli_token: &Option<String>) -> Result<Option<String>> {
     // First check if token was provided via CLI
     if let Some(token) = cli_token {
-        return Some(token.clone());
+        return Ok(Some(token.clone()));
     }
 
-    // Otherwise try to get token from gh_token crate
-    gh_token::get().ok()
-}
+    // Then the token source of the active profile
+    let profile = &settings().profile;
 
-async fn fetch_stars(
-    username: &str,
-    force: bool,
-    token: &Option<String>,
-) -> Result<Vec<StarredRepo>> {
-    // Open database connection
-    let conn = init_db()?;
+    if let Some(var) = &profile.token_env {
+        let token = std::env::var(var).with_context(|| {
+            format!(
+                "Environment variable {} with the GitHub token is not set",
+                var
+            )
+        })?;
+        return Ok(Some(token));
+    }
 
-    // Check if we need to refresh the data
-    if !force {
-        let refresh_needed = match conn.query_row(
-            "SELECT last_updated FROM users WHERE username = ?",
-            params![username],
-            |row| {
-                let last_updated: i64 = row.get(0)?;
-                let now = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs() as i64;
-                Ok(now - last_updated > 86400) // Refresh if older than 1 day
-            },
-        ) {
-            Ok(need_refresh) => need_refresh,
-            Err(rusqlite::Error::QueryReturnedNoRows) => true, // No data, need to fetch
-            Err(e) => return Err(e.into()),
-        };
+    if let Some(command) = &profile.token_command {
+        let output = if cfg!(windows) {
+            std::process::Command::new("cmd")
+                .args(["/C", command])
+                .output()
+        } else {
+            std::process::Command::new("sh")
+                .args(["-c", command])
+                .output()
+        }
+        .with_context(|| format!("Failed to run token command '{}'", command))?;
 
-        if !refresh_needed {
-            println!("Using cached data (less than 1 day old)");
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Token command '{}' failed: {}",
+                command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
 
-            // Fetch cached repos from database
-            let mut stmt = conn.prepare("SELECT json FROM repos WHERE username = ?")?;
-
-            let repos_iter = stmt.query_map(params![username], |row| {
-                let json: String = row.get(0)?;
-                let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        0,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                Ok(repo)
-            })?;
+        return Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ));
+    }
 
-            let mut repos = Vec::new();
+    // Otherwise try to get token from gh_token crate
+    Ok(gh_token::get().ok())
+}
+
+fn github_headers(token: Option<&str>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("gh-stars-cli"));
+
+    if let Some(github_token) = token {
+        let auth_header = format!("token {}", github_token);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&auth_header).context("Invalid GitHub token format")?,
+        );
+    }
+
+    Ok(headers)
+}
+
+// How long to wait before retrying a rate limited request, if it was rate limited
+fn rate_limit_delay(response: &reqwest::Response) -> Option<Duration> {
+    let status = response.status();
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    // Secondary rate limits tell us how long to back off directly
+    if let Some(seconds) = header("retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // Primary rate limit: wait until the window resets
+    if header("x-ratelimit-remaining") == Some(0) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let reset = header("x-ratelimit-reset").unwrap_or(now + 60);
+        return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+    }
+
+    // Secondary rate limit without a hint, GitHub recommends waiting at least a minute
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Some(Duration::from_secs(60));
+    }
+
+    None
+}
+
+// Send a request built by `build`, waiting out GitHub rate limits and retrying
+async fn send_respecting_rate_limit<F>(build: F) -> Result<reqwest::Response>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    for _ in 0..5 {
+        let response = build().send().await?;
+        match rate_limit_delay(&response) {
+            Some(delay) => {
+                println!(
+                    "GitHub rate limit reached, waiting {} seconds...",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            None => return Ok(response),
+        }
+    }
+
+    Err(anyhow!(
+        "GitHub rate limit still exceeded after several retries"
+    ))
+}
+
+// Get the login of the user that owns the token
+async fn get_authenticated_login(client: &reqwest::Client, headers: &HeaderMap) -> Result<String> {
+    #[derive(Deserialize)]
+    struct AuthenticatedUser {
+        login: String,
+    }
+
+    let response = send_respecting_rate_limit(|| {
+        client
+            .get("https://api.github.com/user")
+            .headers(headers.clone())
+    })
+    .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "GitHub API error: {} - {}",
+            response.status(),
+            response.text().await?
+        ));
+    }
+
+    let user: AuthenticatedUser = response.json().await?;
+    Ok(user.login)
+}
+
+// How long a README excerpt is used before it's fetched again
+const README_MAX_AGE_SECS: i64 = 30 * 86400;
+
+// Fetch and store README excerpts for starred repos without a recent one
+async fn fetch_readme_excerpts(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    stars: &[Star],
+) -> Result<()> {
+    let conn = init_db()?;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs() as i64;
+
+    let fresh: HashSet<u64> = {
+        let mut stmt = conn.prepare("SELECT repo_id FROM repo_readmes WHERE fetched_at > ?")?;
+        stmt.query_map(params![now - README_MAX_AGE_SECS], |row| {
+            Ok(row.get::<_, i64>(0)? as u64)
+        })?
+        .collect::<rusqlite::Result<_>>()?
+    };
+    let missing: Vec<&StarredRepo> = stars
+        .iter()
+        .map(|star| &star.repo)
+        .filter(|repo| !fresh.contains(&repo.id))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let progress = progress_bar(missing.len() as u64, "");
+
+    // The raw media type returns the README itself instead of base64 JSON
+    let mut headers = headers.clone();
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github.raw"),
+    );
+
+    for repo in missing {
+        progress.set_message(format!("Fetching README of {}", repo.full_name));
+
+        let url = format!("https://api.github.com/repos/{}/readme", repo.full_name);
+        let response =
+            send_respecting_rate_limit(|| client.get(&url).headers(headers.clone())).await?;
+
+        // Repos without a README get an empty excerpt, so they aren't asked for again
+        let excerpt = if response.status() == StatusCode::NOT_FOUND {
+            String::new()
+        } else if response.status().is_success() {
+            let readme = response.text().await?;
+            readme
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(README_EXCERPT_CHARS)
+                .collect()
+        } else {
+            progress.println(format!(
+                "Failed to fetch README of {}: {}",
+                repo.full_name,
+                response.status()
+            ));
+            progress.inc(1);
+            continue;
+        };
+
+        conn.execute(
+            "INSERT OR REPLACE INTO repo_readmes (repo_id, excerpt, fetched_at) VALUES (?, ?, ?)",
+            params![u64_to_sqlite(repo.id, "repo.id")?, excerpt, now],
+        )?;
+        progress.inc(1);
+    }
+
+    progress.finish_with_message("READMEs fetched");
+    Ok(())
+}
+
+async fn fetch_stars(
+    username: &str,
+    force: bool,
+    token: &Option<String>,
+) -> Result<Vec<StarredRepo>> {
+    // Open database connection
+    let conn = init_db()?;
+
+    // Check if we need to refresh the data
+    if !force {
+        let refresh_needed = match conn.query_row(
+            "SELECT last_updated FROM users WHERE username = ?",
+            params![username],
+            |row| {
+                let last_updated: i64 = row.get(0)?;
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as i64;
+                Ok(now - last_updated > 86400) // Refresh if older than 1 day
+            },
+        ) {
+            Ok(need_refresh) => need_refresh,
+            Err(rusqlite::Error::QueryReturnedNoRows) => true, // No data, need to fetch
+            Err(e) => return Err(e.into()),
+        };
+
+        if !refresh_needed {
+            println!("Using cached data (less than 1 day old)");
+
+            // Fetch cached repos from database
+            let mut stmt = conn.prepare(
+                "SELECT r.json FROM repos r
+                JOIN user_stars s ON s.repo_id = r.id
+                WHERE s.username = ?",
+            )?;
+
+            let repos_iter = stmt.query_map(params![username], repo_from_row)?;
+
+            let mut repos = Vec::new();
             for repo in repos_iter {
                 repos.push(repo?);
             }
@@ -239,22 +1257,24 @@ async fn fetch_stars(
     println!("Fetching stars for GitHub user: {}", username);
 
     let client = reqwest::Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static("gh-stars-cli"));
 
     // Add authentication token if available
-    if let Some(github_token) = get_github_token(token) {
-        let auth_header = format!("token {}", github_token);
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&auth_header).context("Invalid GitHub token format")?,
-        );
+    let github_token = get_github_token(token)?;
+    if github_token.is_some() {
         println!("Using GitHub token for authentication");
     } else {
         println!("No GitHub token found. Using unauthenticated API (rate limits may apply)");
     }
+    let headers = github_headers(github_token.as_deref())?;
+
+    // The star+json media type adds when each repo was starred
+    let mut headers = headers;
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github.star+json"),
+    );
 
-    let mut all_repos = Vec::new();
+    let mut all_stars = Vec::new();
     let mut page = 1;
     let per_page = 100; // Max allowed by GitHub API
 
@@ -278,7 +1298,7 @@ async fn fetch_stars(
         spinner.set_message(format!(
             "Loading page {} (found {} repos so far)",
             page,
-            all_repos.len()
+            all_stars.len()
         ));
 
         // Ensure spinner updates during network calls
@@ -296,15 +1316,24 @@ async fn fetch_stars(
         // Check for pagination before consuming the response body
         let has_more = has_next_page(response.headers());
 
-        // Now parse the JSON response
-        let repos: Vec<StarredRepo> = response.json().await?;
-
-        if repos.is_empty() {
+        // Now parse the JSON response, keeping each repo's full payload
+        let items: Vec<serde_json::Value> = response.json().await?;
+        let stars = items
+            .into_iter()
+            .map(|item| {
+                let payload = item.get("repo").cloned().unwrap_or_default();
+                let mut star: Star = serde_json::from_value(item)?;
+                star.payload = payload;
+                Ok(star)
+            })
+            .collect::<Result<Vec<Star>>>()?;
+
+        if stars.is_empty() {
             break;
         }
 
-        all_repos.extend(repos);
-        spinner.set_message(format!("Found {} repositories so far", all_repos.len()));
+        all_stars.extend(stars);
+        spinner.set_message(format!("Found {} repositories so far", all_stars.len()));
 
         if !has_more {
             break;
@@ -313,7 +1342,13 @@ async fn fetch_stars(
         page += 1;
     }
 
-    spinner.finish_with_message(format!("Fetched {} starred repositories", all_repos.len()));
+    spinner.finish_with_message(format!("Fetched {} starred repositories", all_stars.len()));
+
+    // READMEs are only worth the extra requests when the template embeds them
+    if semantic_enabled() && template_uses(&embedding_template()?, "readme") {
+        let headers = github_headers(github_token.as_deref())?;
+        fetch_readme_excerpts(&client, &headers, &all_stars).await?;
+    }
 
     // Save to database
     let now = SystemTime::now()
@@ -331,11 +1366,24 @@ async fn fetch_stars(
     db_spinner.enable_steady_tick(std::time::Duration::from_millis(100));
     db_spinner.set_message("Storing repos and generating embeddings in database...");
 
-    store_repos_in_db(username, &all_repos, now as i64)?;
+    store_repos_in_db(username, &all_stars, now as i64)?;
 
     db_spinner.finish_with_message(format!("Database updated for user {}", username));
 
-    Ok(all_repos)
+    Ok(all_stars.into_iter().map(|star| star.repo).collect())
+}
+
+// Progress bar with the style shared by every long-running command
+fn progress_bar(len: u64, msg: &str) -> ProgressBar {
+    let progress = ProgressBar::new(len);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("##-"),
+    );
+    progress.set_message(msg.to_string());
+    progress
 }
 
 // Helper function to parse comma-separated languages
@@ -346,6 +1394,18 @@ fn parse_languages(s: &str) -> Result<Vec<String>> {
         .collect())
 }
 
+// Helper function to parse a weight between 0 and 1
+fn parse_weight(s: &str) -> Result<f64> {
+    let weight: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("'{}' is not a number", s))?;
+    if !(0.0..=1.0).contains(&weight) {
+        return Err(anyhow!("The weight must be between 0 and 1"));
+    }
+    Ok(weight)
+}
+
 // Helper function to parse comma-separated usernames
 fn parse_usernames(s: &str) -> Result<Vec<String>> {
     Ok(s.split(',')
@@ -354,352 +1414,3773 @@ fn parse_usernames(s: &str) -> Result<Vec<String>> {
         .collect())
 }
 
-// Store repositories and their embeddings in the database
-fn store_repos_in_db(username: &str, repos: &[StarredRepo], timestamp: i64) -> Result<()> {
-    // Create a progress bar for the embedding process
-    let progress = ProgressBar::new(repos.len() as u64);
-    progress.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
-    progress.set_message("Processing repositories");
+// Delete repos no cached user stars anymore, along with their vectors
+fn collect_garbage(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM repo_vectors WHERE rowid IN (
+            SELECT id FROM repos WHERE id NOT IN (SELECT repo_id FROM user_stars)
+        )",
+        [],
+    )?;
 
-    let mut conn = init_db()?;
+    let removed = conn.execute(
+        "DELETE FROM repos WHERE id NOT IN (SELECT repo_id FROM user_stars)",
+        [],
+    )?;
 
-    // Begin transaction
-    let tx = conn.transaction()?;
+    Ok(removed)
+}
 
-    // Update or insert user
-    tx.execute(
-        "INSERT OR REPLACE INTO users (username, last_updated) VALUES (?, ?)",
-        params![username, timestamp],
-    )?;
+// Model used for repository and query embeddings when the profile doesn't set one
+const DEFAULT_EMBEDDING_MODEL: EmbeddingModel = EmbeddingModel::AllMiniLML6V2;
+
+// APIs of embedding servers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HttpApi {
+    // POST {url}/embeddings, served by OpenAI, vLLM, LM Studio, llama.cpp and others
+    OpenAi,
+    // POST {url}/api/embed
+    Ollama,
+}
+
+impl HttpApi {
+    fn parse(name: &str) -> Option<HttpApi> {
+        match name.to_ascii_lowercase().as_str() {
+            "openai" => Some(HttpApi::OpenAi),
+            "ollama" => Some(HttpApi::Ollama),
+            _ => None,
+        }
+    }
+
+    // Prefix of the model names recorded in the database, e.g. ollama:nomic-embed-text
+    fn prefix(self) -> &'static str {
+        match self {
+            HttpApi::OpenAi => "openai",
+            HttpApi::Ollama => "ollama",
+        }
+    }
+
+    fn default_url(self) -> &'static str {
+        match self {
+            HttpApi::OpenAi => "https://api.openai.com/v1",
+            HttpApi::Ollama => "http://localhost:11434",
+        }
+    }
+}
+
+// Where embeddings are computed
+#[derive(Debug, Clone, PartialEq)]
+enum Backend {
+    // ONNX model run in-process by fastembed
+    FastEmbed(EmbeddingModel),
+    // ONNX model and tokenizer in a local directory, run by fastembed
+    Local(PathBuf),
+    // Model served by an embedding server
+    Http { api: HttpApi, model: String },
+}
+
+impl Backend {
+    fn api(&self) -> Option<HttpApi> {
+        match self {
+            Backend::FastEmbed(_) | Backend::Local(_) => None,
+            Backend::Http { api, .. } => Some(*api),
+        }
+    }
+
+    // Name recorded in the database for vectors of this model
+    fn name(&self) -> Result<String> {
+        match self {
+            Backend::FastEmbed(model) => Ok(TextEmbedding::get_model_info(model)
+                .map_err(|e| anyhow!("Failed to look up embedding model: {}", e))?
+                .model_code
+                .clone()),
+            // Exported models keep the name of the model they were exported from
+            Backend::Local(path) => Ok(match read_model_manifest(path)?.name {
+                Some(name) => name,
+                None => format!(
+                    "local:{}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ),
+            }),
+            Backend::Http { api, model } => Ok(format!("{}:{}", api.prefix(), model)),
+        }
+    }
+}
+
+// An embedding model with the name and dimension recorded in the database
+struct ModelSpec {
+    backend: Backend,
+    name: String,
+    dimension: usize,
+}
+
+// Look up a fastembed model by model code. Quantized variants share their
+// model code, so fastembed's variant names (e.g. BGESmallENV15Q) are accepted as well.
+fn parse_fastembed_model(name: &str) -> Result<EmbeddingModel> {
+    name.parse::<EmbeddingModel>()
+        .ok()
+        .or_else(|| {
+            TextEmbedding::list_supported_models()
+                .into_iter()
+                .map(|info| info.model)
+                .find(|model| format!("{:?}", model).eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown embedding model '{}'. Use a fastembed model code such as Xenova/bge-small-en-v1.5 or a variant name such as BGESmallENV15, or a server model such as ollama:nomic-embed-text.",
+                name
+            )
+        })
+}
+
+// Parse a model name as recorded in the database: `openai:<model>` and
+// `ollama:<model>` are served by an embedding server, anything else is a fastembed model
+fn parse_model(name: &str) -> Result<Backend> {
+    if let Some((prefix, model)) = name.split_once(':')
+        && let Some(api) = HttpApi::parse(prefix)
+    {
+        return Ok(Backend::Http {
+            api,
+            model: model.to_string(),
+        });
+    }
+
+    parse_fastembed_model(name).map(Backend::FastEmbed)
+}
+
+// The embedding_backend setting, None for fastembed
+fn configured_api() -> Result<Option<HttpApi>> {
+    match settings().profile.embedding_backend.as_deref() {
+        None => Ok(None),
+        Some(name) if name.eq_ignore_ascii_case("fastembed") => Ok(None),
+        Some(name) => HttpApi::parse(name).map(Some).ok_or_else(|| {
+            anyhow!(
+                "Unknown embedding backend '{}'. Use fastembed, openai or ollama.",
+                name
+            )
+        }),
+    }
+}
+
+// Parse a model name from the config or --model, served by the configured backend
+fn parse_configured_model(name: &str) -> Result<Backend> {
+    match configured_api()? {
+        Some(api) => {
+            let model = name
+                .strip_prefix(&format!("{}:", api.prefix()))
+                .unwrap_or(name);
+            Ok(Backend::Http {
+                api,
+                model: model.to_string(),
+            })
+        }
+        None => parse_model(name),
+    }
+}
+
+// Look up the name and dimension of a model. The dimension of a server model
+// is taken from embedding_dimension, else from `stored` when it names the same
+// model, else from a probe embedding.
+fn model_spec(backend: Backend, stored: Option<(String, usize)>) -> Result<ModelSpec> {
+    let name = backend.name()?;
+    let dimension = match &backend {
+        Backend::FastEmbed(model) => {
+            TextEmbedding::get_model_info(model)
+                .map_err(|e| anyhow!("Failed to look up embedding model: {}", e))?
+                .dim
+        }
+        Backend::Local(path) => {
+            let manifest = read_model_manifest(path)?;
+            match (
+                manifest.dimension,
+                settings().profile.embedding_dimension,
+                stored,
+            ) {
+                (Some(dimension), _, _) | (None, Some(dimension), _) => dimension,
+                (None, None, Some((stored_name, dimension))) if stored_name == name => dimension,
+                _ => probe_dimension(&mut FastEmbedder(load_local_model(path)?))?,
+            }
+        }
+        Backend::Http { api, model } => match (settings().profile.embedding_dimension, stored) {
+            (Some(dimension), _) => dimension,
+            (None, Some((stored_name, dimension))) if stored_name == name => dimension,
+            _ => probe_dimension(&mut HttpEmbedder::new(*api, model, None)?)?,
+        },
+    };
+
+    Ok(ModelSpec {
+        backend,
+        name,
+        dimension,
+    })
+}
 
-    // Get all repo IDs for this user before deleting repos
-    let repo_ids: Vec<i64> = {
-        let mut stmt = tx.prepare("SELECT id FROM repos WHERE username = ?")?;
-        stmt.query_map(params![username], |row| row.get(0))?
-            .collect::<Result<Vec<i64>, _>>()?
+// Length of the vectors an embedder returns
+fn probe_dimension(embedder: &mut dyn Embedder) -> Result<usize> {
+    embedder
+        .embed(&["dimension probe"])?
+        .first()
+        .map(Vec::len)
+        .ok_or_else(|| anyhow!("The embedder returned no embedding"))
+}
+
+// Resolve the embedding model for new vectors and queries: a local model,
+// else the profile's setting, else the model of the stored vectors if the
+// configured backend serves it, else the backend's default
+fn configured_model(conn: &Connection) -> Result<ModelSpec> {
+    let stored = stored_model(conn)?;
+    let profile = &settings().profile;
+    let backend = match (&profile.embedding_model_path, &profile.embedding_model) {
+        (Some(path), _) if configured_api()?.is_none() => Backend::Local(expand_home(path)),
+        (_, Some(name)) => parse_configured_model(name)?,
+        (_, None) => {
+            let api = configured_api()?;
+            match stored.as_ref().and_then(|(name, _)| parse_model(name).ok()) {
+                Some(backend)
+                    if settings().profile.embedding_backend.is_none() || backend.api() == api =>
+                {
+                    backend
+                }
+                _ => match api {
+                    None => Backend::FastEmbed(DEFAULT_EMBEDDING_MODEL),
+                    Some(api) => {
+                        return Err(anyhow!(
+                            "embedding_backend = \"{}\" needs embedding_model to be set in the config",
+                            api.prefix()
+                        ));
+                    }
+                },
+            }
+        }
     };
 
-    // Clear existing data for this user
-    tx.execute("DELETE FROM repos WHERE username = ?", params![username])?;
+    model_spec(backend, stored)
+}
+
+fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    match conn.query_row(
+        "SELECT value FROM meta WHERE key = ?",
+        params![key],
+        |row| row.get(0),
+    ) {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+// Name and dimension of the model the stored vectors were made with
+fn stored_model(conn: &Connection) -> Result<Option<(String, usize)>> {
+    let name = get_meta(conn, "embedding_model")?;
+    let dimension = get_meta(conn, "embedding_dimension")?.and_then(|d| d.parse().ok());
+    Ok(name.zip(dimension))
+}
+
+// How repo_vectors stores the vectors. Quantized vectors keep the full
+// precision vector in an auxiliary column to rerank the nearest candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VectorStorage {
+    /// 4 bytes per dimension, exact search
+    Float,
+    /// 1 byte per dimension, for vectors with values between -1 and 1
+    Int8,
+    /// 1 bit per dimension, for dimensions divisible by 8
+    Binary,
+}
+
+// Quantized search reranks this many candidates per requested result
+const RERANK_FACTOR: usize = 10;
 
-    // Clear existing vectors for this user's repos
-    if !repo_ids.is_empty() {
-        // For each ID, delete the corresponding vector
-        for id in repo_ids {
-            tx.execute("DELETE FROM repo_vectors WHERE rowid = ?", params![id])?;
+impl VectorStorage {
+    const ALL: [VectorStorage; 3] = [Self::Float, Self::Int8, Self::Binary];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Int8 => "int8",
+            Self::Binary => "binary",
         }
     }
 
-    // Initialize the embedder
-    let mut embedder = TextEmbedding::try_new(
-        InitOptions::new(EmbeddingModel::AllMiniLML6V2).with_show_download_progress(true),
-    )
-    .map_err(|e| anyhow!("Failed to initialize embedder: {}", e))?;
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|storage| storage.name() == name)
+    }
 
-    for (i, repo) in repos.iter().enumerate() {
-        // Update progress bar
-        progress.set_position(i as u64);
-        if i % 10 == 0 || i == repos.len() - 1 {
-            progress.set_message(format!("Processed {}/{} repositories", i + 1, repos.len()));
+    // Size of the searched embedding column
+    fn index_bytes(self, dimension: usize) -> usize {
+        match self {
+            Self::Float => dimension * 4,
+            Self::Int8 => dimension,
+            Self::Binary => dimension / 8,
         }
+    }
 
-        // Insert repo data
-        tx.execute(
-            "INSERT INTO repos
-            (id, username, full_name, name, owner, html_url, description, language, stars, forks, open_issues, updated_at, created_at, json)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params![
-                u64_to_sqlite(repo.id, "repo.id")?,
-                username,
-                repo.full_name,
-                repo.name,
-                repo.owner.login,
-                repo.html_url,
-                repo.description,
-                repo.language,
-                u64_to_sqlite(repo.stargazers_count, "repo.stargazers_count")?,
-                optional_u64_to_sqlite(repo.forks_count, "repo.forks_count")?,
-                optional_u64_to_sqlite(repo.open_issues_count, "repo.open_issues_count")?,
-                repo.updated_at,
-                repo.created_at,
-                serde_json::to_string(repo)?
-            ],
-        )?;
+    // Size of a stored vector, including the full precision copy kept for reranking
+    fn bytes_per_vector(self, dimension: usize) -> usize {
+        match self {
+            Self::Float => self.index_bytes(dimension),
+            _ => self.index_bytes(dimension) + dimension * 4,
+        }
+    }
 
-        // Create text for embedding (combine name and description)
-        let embed_text = format!(
-            "{} {} {}",
-            repo.name,
-            repo.language.as_deref().unwrap_or(""),
-            repo.description.as_deref().unwrap_or("")
-        );
+    fn check_dimension(self, dimension: usize) -> Result<()> {
+        if self == Self::Binary && !dimension.is_multiple_of(8) {
+            return Err(anyhow!(
+                "Binary vectors need a dimension divisible by 8, the model has {} dimensions",
+                dimension
+            ));
+        }
+        Ok(())
+    }
+
+    fn columns(self, dimension: usize) -> String {
+        match self {
+            Self::Float => format!("embedding float[{dimension}]"),
+            Self::Int8 => format!("embedding int8[{dimension}], +full blob"),
+            Self::Binary => format!("embedding bit[{dimension}], +full blob"),
+        }
+    }
+
+    // SQL turning the float vector parameter `param` into a stored embedding
+    fn quantize(self, param: &str) -> String {
+        match self {
+            Self::Float => param.to_string(),
+            Self::Int8 => format!("vec_quantize_int8({param}, 'unit')"),
+            Self::Binary => format!("vec_quantize_binary({param})"),
+        }
+    }
+
+    // Column with the full precision vector
+    fn full_column(self) -> &'static str {
+        match self {
+            Self::Float => "embedding",
+            _ => "full",
+        }
+    }
+}
+
+fn vector_storage(conn: &Connection) -> Result<VectorStorage> {
+    match get_meta(conn, "vector_storage")? {
+        None => Ok(VectorStorage::Float),
+        Some(name) => VectorStorage::parse(&name)
+            .ok_or_else(|| anyhow!("Unknown vector storage '{}' in the database", name)),
+    }
+}
+
+fn create_vector_table(
+    conn: &Connection,
+    table: &str,
+    storage: VectorStorage,
+    dimension: usize,
+) -> Result<()> {
+    storage.check_dimension(dimension)?;
+    conn.execute_batch(&format!(
+        "DROP TABLE IF EXISTS {table};
+        CREATE VIRTUAL TABLE {table} USING vec0(
+            {}
+        );",
+        storage.columns(dimension)
+    ))?;
+    Ok(())
+}
+
+// Insert a little-endian f32 vector, quantizing it for the storage
+fn insert_vector(
+    conn: &Connection,
+    table: &str,
+    storage: VectorStorage,
+    rowid: i64,
+    vector: &[u8],
+) -> Result<()> {
+    let sql = match storage {
+        VectorStorage::Float => format!("INSERT INTO {table}(rowid, embedding) VALUES (?1, ?2)"),
+        _ => format!(
+            "INSERT INTO {table}(rowid, embedding, full) VALUES (?1, {}, ?2)",
+            storage.quantize("?2")
+        ),
+    };
+    conn.prepare_cached(&sql)?.execute(params![rowid, vector])?;
+    Ok(())
+}
+
+// Every stored vector in full precision
+fn full_vectors(
+    conn: &Connection,
+    table: &str,
+    storage: VectorStorage,
+) -> Result<Vec<(i64, Vec<u8>)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT rowid, {} FROM {table} ORDER BY rowid",
+        storage.full_column()
+    ))?;
+    let vectors = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(vectors)
+}
+
+// Nearest neighbours of the float vector bound to ?1, as (rowid, distance).
+// `candidates` is an optional query selecting the rowids to search, which
+// sqlite-vec applies while scanning, so filtered searches still find `limit`
+// neighbours. Quantized vectors only preselect candidates, which are reranked
+// by their full precision distance.
+fn knn_sql(table: &str, storage: VectorStorage, limit: usize, candidates: Option<&str>) -> String {
+    let filter = candidates
+        .map(|candidates| format!(" AND rowid IN ({candidates})"))
+        .unwrap_or_default();
+    match storage {
+        VectorStorage::Float => format!(
+            "SELECT rowid, distance
+            FROM {table}
+            WHERE embedding MATCH ?1{filter}
+            ORDER BY distance
+            LIMIT {limit}"
+        ),
+        _ => format!(
+            "SELECT rowid, vec_distance_l2(full, ?1) AS distance
+            FROM (
+                SELECT rowid, full
+                FROM {table}
+                WHERE embedding MATCH {}{filter}
+                ORDER BY distance
+                LIMIT {}
+            )
+            ORDER BY distance
+            LIMIT {limit}",
+            storage.quantize("?1"),
+            limit * RERANK_FACTOR
+        ),
+    }
+}
+
+// Drop every vector and size repo_vectors for the given model, keeping the
+// vector storage. The vectors stored afterwards are made with the current
+// template.
+fn reset_vectors(conn: &Connection, spec: &ModelSpec) -> Result<()> {
+    create_vector_table(conn, "repo_vectors", vector_storage(conn)?, spec.dimension)?;
+    conn.execute("DELETE FROM repo_vector_hashes", [])?;
+    set_meta(conn, "embedding_model", &spec.name)?;
+    set_meta(conn, "embedding_dimension", &spec.dimension.to_string())?;
+    set_meta(conn, "embedding_template", &embedding_template()?)?;
+    Ok(())
+}
+
+// Make sure new vectors made with `spec` can be stored next to the existing ones.
+// An empty vector table is resized for the model, existing vectors of another
+// model are never mixed with new ones.
+fn ensure_vector_model(conn: &Connection, spec: &ModelSpec) -> Result<()> {
+    if let Some((name, dimension)) = stored_model(conn)?
+        && name == spec.name
+        && dimension == spec.dimension
+    {
+        return Ok(());
+    }
+
+    if count(conn, "SELECT COUNT(*) FROM repo_vectors")? == 0 {
+        return reset_vectors(conn, spec);
+    }
+
+    Err(model_mismatch_error(conn, spec))
+}
+
+fn model_mismatch_error(conn: &Connection, spec: &ModelSpec) -> anyhow::Error {
+    let stored = stored_model(conn).ok().flatten();
+    // Same model, but embedding_dimension asks for another vector length
+    if let Some((name, dimension)) = &stored
+        && *name == spec.name
+    {
+        return anyhow!(
+            "The stored vectors of {} have {} dimensions, but {} are configured. Run 'gh-stars reembed' to re-embed them, or set embedding_dimension = {} in the config.",
+            name,
+            dimension,
+            spec.dimension,
+            dimension
+        );
+    }
+    let stored = stored
+        .map(|(name, _)| name)
+        .unwrap_or_else(|| "an unknown model".to_string());
+    anyhow!(
+        "The stored vectors were made with {}, but the configured embedding model is {}. Run 'gh-stars reembed' to re-embed with {}, or set embedding_model = \"{}\" in the config.",
+        stored,
+        spec.name,
+        spec.name,
+        stored
+    )
+}
+
+// Computes the embeddings of repository and query texts
+trait Embedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+}
+
+struct FastEmbedder(TextEmbedding);
+
+impl Embedder for FastEmbedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.0
+            .embed(texts.to_vec(), Some(texts.len()))
+            .map_err(|e| anyhow!("Embedding failed: {}", e))
+    }
+}
+
+// How long to wait for an embedding server to answer one batch
+const HTTP_EMBED_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Embeds texts with an OpenAI-compatible or Ollama embedding server
+struct HttpEmbedder {
+    client: reqwest::Client,
+    api: HttpApi,
+    url: String,
+    model: String,
+    headers: HeaderMap,
+    // Expected vector length, checked against every response
+    dimension: Option<usize>,
+    // Sent as `dimensions`, only with request_dimensions
+    requested_dimension: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddings {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddings {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl HttpEmbedder {
+    fn new(api: HttpApi, model: &str, dimension: Option<usize>) -> Result<HttpEmbedder> {
+        let profile = &settings().profile;
+        let url = profile
+            .embedding_url
+            .as_deref()
+            .unwrap_or(api.default_url())
+            .trim_end_matches('/')
+            .to_string();
+
+        // The key is sent as a bearer token unless another header is configured
+        let mut headers = HeaderMap::new();
+        if let Some(var) = &profile.embedding_api_key_env {
+            let key = std::env::var(var).with_context(|| {
+                format!(
+                    "The environment variable {} with the embedding API key isn't set",
+                    var
+                )
+            })?;
+            let (name, value) = match &profile.embedding_auth_header {
+                Some(name) => (
+                    HeaderName::from_bytes(name.as_bytes())
+                        .with_context(|| format!("Invalid embedding_auth_header '{}'", name))?,
+                    key,
+                ),
+                None => (AUTHORIZATION, format!("Bearer {}", key)),
+            };
+            headers.insert(
+                name,
+                HeaderValue::from_str(&value)
+                    .context("The embedding API key isn't a valid header value")?,
+            );
+        }
+
+        // Many OpenAI-compatible servers reject `dimensions`, so it is opt-in.
+        // text-embedding-3 models use it to shorten their vectors.
+        let requested_dimension = match (profile.request_dimensions, profile.embedding_dimension) {
+            (Some(true), _) if api != HttpApi::OpenAi => {
+                return Err(anyhow!(
+                    "request_dimensions is only supported with the openai backend"
+                ));
+            }
+            (Some(true), None) => {
+                return Err(anyhow!(
+                    "request_dimensions needs embedding_dimension to be set"
+                ));
+            }
+            (Some(true), dimension) => dimension,
+            _ => None,
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(HTTP_EMBED_TIMEOUT)
+            .build()?;
+
+        Ok(HttpEmbedder {
+            client,
+            api,
+            url,
+            model: model.to_string(),
+            headers,
+            dimension,
+            requested_dimension,
+        })
+    }
+
+    async fn request(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let (endpoint, mut body) = match self.api {
+            HttpApi::OpenAi => (
+                format!("{}/embeddings", self.url),
+                serde_json::json!({ "model": self.model, "input": texts }),
+            ),
+            HttpApi::Ollama => (
+                format!("{}/api/embed", self.url),
+                serde_json::json!({ "model": self.model, "input": texts }),
+            ),
+        };
+        if let Some(dimension) = self.requested_dimension {
+            body["dimensions"] = dimension.into();
+        }
+
+        let response = self
+            .client
+            .post(&endpoint)
+            .headers(self.headers.clone())
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach the embedding server at {}", endpoint))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "The embedding server at {} returned {}: {}",
+                endpoint,
+                status,
+                text.trim()
+            ));
+        }
+
+        let embeddings = match self.api {
+            HttpApi::OpenAi => {
+                let mut data = response.json::<OpenAiEmbeddings>().await?.data;
+                data.sort_by_key(|e| e.index);
+                data.into_iter().map(|e| e.embedding).collect()
+            }
+            HttpApi::Ollama => response.json::<OllamaEmbeddings>().await?.embeddings,
+        };
+        Ok(embeddings)
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Callers are synchronous code running on the multi-threaded runtime
+        let embeddings = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.request(texts))
+        })?;
+
+        if embeddings.len() != texts.len() {
+            return Err(anyhow!(
+                "The embedding server returned {} embeddings for {} texts",
+                embeddings.len(),
+                texts.len()
+            ));
+        }
+        if let Some(dimension) = self.dimension
+            && let Some(embedding) = embeddings.iter().find(|e| e.len() != dimension)
+        {
+            return Err(anyhow!(
+                "The embedding server returned {} dimensions, but {} were expected. Check embedding_dimension in the config.",
+                embedding.len(),
+                dimension
+            ));
+        }
+
+        Ok(embeddings)
+    }
+}
+
+// Initialize the embedder for a model, using the embedder daemon when it
+// serves the model. Otherwise the model is loaded in-process.
+fn create_embedder(spec: &ModelSpec) -> Result<Box<dyn Embedder>> {
+    if spec.backend.api().is_none()
+        && let Some(embedder) = daemon_embedder(spec)
+    {
+        return Ok(embedder);
+    }
+
+    Ok(load_embedder(spec)?)
+}
+
+// Initialize the embedder for a model in this process. fastembed models are
+// downloaded to the model cache on first use.
+fn load_embedder(spec: &ModelSpec) -> Result<Box<dyn Embedder + Send>> {
+    match &spec.backend {
+        Backend::FastEmbed(model) => Ok(Box::new(FastEmbedder(load_fastembed_model(model)?))),
+        Backend::Local(path) => Ok(Box::new(FastEmbedder(load_local_model(path)?))),
+        Backend::Http { api, model } => Ok(Box::new(HttpEmbedder::new(
+            *api,
+            model,
+            Some(spec.dimension),
+        )?)),
+    }
+}
+
+// Get the directory fastembed models are downloaded to. fastembed prefers
+// HF_HOME when it is set, so it is honored here too.
+fn model_cache_dir() -> Result<PathBuf> {
+    if let Ok(path) = std::env::var("HF_HOME") {
+        return Ok(PathBuf::from(path));
+    }
+
+    let path = match &settings().profile.model_cache_dir {
+        Some(path) => expand_home(path),
+        None => get_cache_dir()?.join("models"),
+    };
+    create_dir_all(&path)?;
+    Ok(path)
+}
+
+// Load a fastembed model, downloading it to the model cache on first use
+fn load_fastembed_model(model: &EmbeddingModel) -> Result<TextEmbedding> {
+    TextEmbedding::try_new(
+        InitOptions::new(model.clone())
+            .with_show_download_progress(true)
+            .with_cache_dir(model_cache_dir()?),
+    )
+    .map_err(|e| anyhow!("Failed to initialize embedder: {}", e))
+}
+
+// Files of a local model directory
+const MODEL_MANIFEST: &str = "gh-stars-model.json";
+const MODEL_FILE: &str = "model.onnx";
+const TOKENIZER_FILES: [&str; 4] = [
+    "tokenizer.json",
+    "config.json",
+    "special_tokens_map.json",
+    "tokenizer_config.json",
+];
+
+// Optional description of a local model, written by `model export`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ModelManifest {
+    // Name recorded in the database, defaults to local:<directory name>
+    name: Option<String>,
+    // Vector length, probed when unset
+    dimension: Option<usize>,
+    // Pooling of the token embeddings: mean (default) or cls
+    pooling: Option<String>,
+    // Quantization of the ONNX model: none (default), static or dynamic
+    quantization: Option<String>,
+}
+
+fn read_model_manifest(dir: &Path) -> Result<ModelManifest> {
+    let path = dir.join(MODEL_MANIFEST);
+    if !path.exists() {
+        return Ok(ModelManifest::default());
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn read_model_file(dir: &Path, name: &str) -> Result<Vec<u8>> {
+    let path = dir.join(name);
+    std::fs::read(&path).with_context(|| format!("Failed to read model file {}", path.display()))
+}
+
+// Load the ONNX model and tokenizer in `dir`
+fn load_local_model(dir: &Path) -> Result<TextEmbedding> {
+    let manifest = read_model_manifest(dir)?;
+    let pooling = match manifest
+        .pooling
+        .as_deref()
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        None | Some("mean") => Pooling::Mean,
+        Some("cls") => Pooling::Cls,
+        Some(other) => {
+            return Err(anyhow!(
+                "Unknown pooling '{}' in {}, use mean or cls",
+                other,
+                dir.join(MODEL_MANIFEST).display()
+            ));
+        }
+    };
+    let quantization = match manifest
+        .quantization
+        .as_deref()
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        None | Some("none") => QuantizationMode::None,
+        Some("static") => QuantizationMode::Static,
+        Some("dynamic") => QuantizationMode::Dynamic,
+        Some(other) => {
+            return Err(anyhow!(
+                "Unknown quantization '{}' in {}, use none, static or dynamic",
+                other,
+                dir.join(MODEL_MANIFEST).display()
+            ));
+        }
+    };
+
+    let [tokenizer, config, special_tokens_map, tokenizer_config] = TOKENIZER_FILES;
+    let tokenizer_files = TokenizerFiles {
+        tokenizer_file: read_model_file(dir, tokenizer)?,
+        config_file: read_model_file(dir, config)?,
+        special_tokens_map_file: read_model_file(dir, special_tokens_map)?,
+        tokenizer_config_file: read_model_file(dir, tokenizer_config)?,
+    };
+    let model = UserDefinedEmbeddingModel::new(read_model_file(dir, MODEL_FILE)?, tokenizer_files)
+        .with_pooling(pooling)
+        .with_quantization(quantization);
+
+    TextEmbedding::try_new_from_user_defined(model, InitOptionsUserDefined::new())
+        .map_err(|e| anyhow!("Failed to load the model in {}: {}", dir.display(), e))
+}
+
+// Path of a downloaded model file, in the Hugging Face cache layout
+fn cached_model_file(model_code: &str, file: &str) -> Result<PathBuf> {
+    let repo = model_cache_dir()?.join(format!("models--{}", model_code.replace('/', "--")));
+    let revision = std::fs::read_to_string(repo.join("refs").join("main"))
+        .with_context(|| format!("{} isn't in the model cache", model_code))?;
+    Ok(repo.join("snapshots").join(revision.trim()).join(file))
+}
+
+// Requests sent to the embedder daemon, one JSON object per line
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum DaemonRequest {
+    Embed { model: String, texts: Vec<String> },
+    Status,
+    Stop,
+}
+
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DaemonResponse {
+    Embeddings(Vec<Vec<f32>>),
+    Status(DaemonStatus),
+    Stopping,
+    Error(String),
+}
+
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonStatus {
+    model: String,
+    pid: u32,
+    started_at: i64,
+    requests: u64,
+    texts: u64,
+}
+
+// Give up on a starting daemon after this long, loading a model can include downloading it
+#[cfg(unix)]
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(300);
+
+// The daemon's socket and log live in a directory only the user can enter,
+// so other local users can't connect even before the socket is chmodded
+#[cfg(unix)]
+fn daemon_dir() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join("daemon"))
+}
+
+#[cfg(unix)]
+fn create_daemon_dir() -> Result<PathBuf> {
+    let dir = daemon_dir()?;
+    create_dir_all(&dir)?;
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn daemon_socket_path() -> Result<PathBuf> {
+    Ok(daemon_dir()?.join("embedder.sock"))
+}
+
+// One connection to the embedder daemon
+#[cfg(unix)]
+struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+#[cfg(unix)]
+impl DaemonClient {
+    fn connect() -> Option<DaemonClient> {
+        let stream = UnixStream::connect(daemon_socket_path().ok()?).ok()?;
+        Some(DaemonClient {
+            reader: BufReader::new(stream.try_clone().ok()?),
+            writer: stream,
+        })
+    }
+
+    fn request(&mut self, request: &DaemonRequest) -> Result<DaemonResponse> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        line.clear();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("The embedder daemon closed the connection"));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+// Embeds texts with the model loaded by the embedder daemon
+#[cfg(unix)]
+struct DaemonEmbedder {
+    client: DaemonClient,
+    model: String,
+}
+
+#[cfg(unix)]
+impl Embedder for DaemonEmbedder {
+    fn embed(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let request = DaemonRequest::Embed {
+            model: self.model.clone(),
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+        };
+        match self.client.request(&request)? {
+            DaemonResponse::Embeddings(embeddings) => Ok(embeddings),
+            DaemonResponse::Error(message) => {
+                Err(anyhow!("The embedder daemon failed: {}", message))
+            }
+            response => Err(anyhow!(
+                "Unexpected response from the embedder daemon: {:?}",
+                response
+            )),
+        }
+    }
+}
+
+// The running daemon as an embedder, if it serves the model of `spec`
+#[cfg(unix)]
+fn daemon_embedder(spec: &ModelSpec) -> Option<Box<dyn Embedder>> {
+    let mut embedder = DaemonEmbedder {
+        client: DaemonClient::connect()?,
+        model: spec.name.clone(),
+    };
+    // A daemon serving another model refuses the empty request
+    embedder.embed(&[]).ok()?;
+    Some(Box::new(embedder))
+}
+
+#[cfg(not(unix))]
+fn daemon_embedder(_spec: &ModelSpec) -> Option<Box<dyn Embedder>> {
+    None
+}
+
+// Status of the running daemon, None when there is none
+#[cfg(unix)]
+fn daemon_status() -> Result<Option<DaemonStatus>> {
+    let Some(mut client) = DaemonClient::connect() else {
+        return Ok(None);
+    };
+    match client.request(&DaemonRequest::Status)? {
+        DaemonResponse::Status(status) => Ok(Some(status)),
+        response => Err(anyhow!(
+            "Unexpected response from the embedder daemon: {:?}",
+            response
+        )),
+    }
+}
+
+// Answer the requests of one client until it disconnects
+#[cfg(unix)]
+fn serve_daemon_client(
+    stream: UnixStream,
+    embedder: &Mutex<Box<dyn Embedder + Send>>,
+    status: &Mutex<DaemonStatus>,
+    socket: &Path,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let mut stop = false;
+        let response = match serde_json::from_str(&line) {
+            Ok(DaemonRequest::Embed { model, texts }) => {
+                let serving = status.lock().unwrap().model.clone();
+                if model != serving {
+                    DaemonResponse::Error(format!("The daemon serves {}, not {}", serving, model))
+                } else {
+                    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                    let result = embedder.lock().unwrap().embed(&texts);
+                    let mut status = status.lock().unwrap();
+                    status.requests += 1;
+                    status.texts += texts.len() as u64;
+                    match result {
+                        Ok(embeddings) => DaemonResponse::Embeddings(embeddings),
+                        Err(e) => DaemonResponse::Error(format!("{:#}", e)),
+                    }
+                }
+            }
+            Ok(DaemonRequest::Status) => {
+                let status = status.lock().unwrap();
+                DaemonResponse::Status(DaemonStatus {
+                    model: status.model.clone(),
+                    ..*status
+                })
+            }
+            Ok(DaemonRequest::Stop) => {
+                stop = true;
+                DaemonResponse::Stopping
+            }
+            Err(e) => DaemonResponse::Error(format!("Invalid request: {}", e)),
+        };
+
+        let mut reply = serde_json::to_string(&response)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes())?;
+        line.clear();
+
+        if stop {
+            std::fs::remove_file(socket).ok();
+            println!("Stopped");
+            std::process::exit(0);
+        }
+    }
+
+    Ok(())
+}
+
+// Load the configured model and serve embeddings on the daemon socket until stopped
+#[cfg(unix)]
+fn run_daemon() -> Result<()> {
+    let spec = configured_model(&init_db()?)?;
+    if let Backend::Http { .. } = spec.backend {
+        return Err(anyhow!(
+            "{} is computed on a server, the embedder daemon only helps with local models",
+            spec.name
+        ));
+    }
+
+    create_daemon_dir()?;
+    let socket = daemon_socket_path()?;
+    if daemon_status()?.is_some() {
+        return Err(anyhow!("The embedder daemon is already running"));
+    }
+    // Left behind by a daemon that didn't shut down cleanly
+    std::fs::remove_file(&socket).ok();
+
+    let embedder: Mutex<Box<dyn Embedder + Send>> = Mutex::new(load_embedder(&spec)?);
+    let status = Mutex::new(DaemonStatus {
+        model: spec.name.clone(),
+        pid: std::process::id(),
+        started_at: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs() as i64,
+        requests: 0,
+        texts: 0,
+    });
+
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+    println!("Serving {} on {}", spec.name, socket.display());
+
+    // Clients are served concurrently, embedding one request at a time
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (embedder, status, socket) = (&embedder, &status, &socket);
+                    scope.spawn(move || {
+                        if let Err(e) = serve_daemon_client(stream, embedder, status, socket) {
+                            eprintln!("Client failed: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept a client: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Run the daemon in the background and wait until it serves requests
+#[cfg(unix)]
+fn start_daemon() -> Result<()> {
+    if let Some(status) = daemon_status()? {
+        println!(
+            "The embedder daemon is already running (pid {}, {})",
+            status.pid, status.model
+        );
+        return Ok(());
+    }
+
+    let log_path = create_daemon_dir()?.join("embedder.log");
+    let log = std::fs::File::create(&log_path)?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    if let Some(db) = &settings().db_path {
+        command.arg("--db").arg(db);
+    }
+    if let Some(profile) = &settings().profile_name {
+        command.arg("--profile").arg(profile);
+    }
+    // Its own process group keeps the daemon alive when the terminal closes
+    let mut child = command
+        .args(["daemon", "run"])
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .process_group(0)
+        .spawn()?;
+
+    let started = std::time::Instant::now();
+    while daemon_status()?.is_none() {
+        if let Some(exit) = child.try_wait()? {
+            return Err(anyhow!(
+                "The embedder daemon exited with {}, see {}",
+                exit,
+                log_path.display()
+            ));
+        }
+        if started.elapsed() > DAEMON_START_TIMEOUT {
+            return Err(anyhow!(
+                "The embedder daemon didn't start in time, see {}",
+                log_path.display()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    println!(
+        "Started the embedder daemon (pid {}), logging to {}",
+        child.id(),
+        log_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+fn stop_daemon() -> Result<()> {
+    let Some(mut client) = DaemonClient::connect() else {
+        println!("The embedder daemon isn't running");
+        return Ok(());
+    };
+    match client.request(&DaemonRequest::Stop)? {
+        DaemonResponse::Stopping => println!("Stopped the embedder daemon"),
+        response => {
+            return Err(anyhow!(
+                "Unexpected response from the embedder daemon: {:?}",
+                response
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn show_daemon_status() -> Result<()> {
+    match daemon_status()? {
+        Some(status) => {
+            println!("Running (pid {})", status.pid);
+            println!("Model: {}", status.model);
+            println!("Started: {}", format_age(status.started_at));
+            println!(
+                "Served: {} requests, {} texts",
+                status.requests, status.texts
+            );
+        }
+        None => println!("The embedder daemon isn't running"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn daemon_command(command: &DaemonCommands) -> Result<()> {
+    match command {
+        DaemonCommands::Start => start_daemon(),
+        DaemonCommands::Run => run_daemon(),
+        DaemonCommands::Stop => stop_daemon(),
+        DaemonCommands::Status => show_daemon_status(),
+    }
+}
+
+#[cfg(not(unix))]
+fn daemon_command(_command: &DaemonCommands) -> Result<()> {
+    Err(anyhow!("The embedder daemon needs Unix domain sockets"))
+}
+
+// Texts embedded per call when the profile doesn't set embed_batch_size
+const DEFAULT_EMBED_BATCH_SIZE: usize = 64;
+
+// Embed texts in batches, advancing the progress bar after each batch. ONNX
+// Runtime spreads every batch across all CPU cores, so larger batches keep
+// them busy, at the cost of memory. Embedding servers get one request per batch.
+fn embed_texts(
+    embedder: &mut dyn Embedder,
+    texts: Vec<String>,
+    progress: &ProgressBar,
+) -> Result<Vec<Vec<f32>>> {
+    let batch_size = settings()
+        .profile
+        .embed_batch_size
+        .unwrap_or(DEFAULT_EMBED_BATCH_SIZE)
+        .max(1);
+
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(batch_size) {
+        let batch_embeddings =
+            embedder.embed(&batch.iter().map(String::as_str).collect::<Vec<_>>())?;
+        embeddings.extend(batch_embeddings);
+
+        progress.inc(batch.len() as u64);
+        progress.set_message(format!(
+            "Embedded {}/{} repositories",
+            embeddings.len(),
+            texts.len()
+        ));
+    }
+
+    Ok(embeddings)
+}
+
+// Text embedded for each repo when the profile doesn't set embedding_template
+const DEFAULT_EMBEDDING_TEMPLATE: &str = "{name} {language} {description}";
+
+// Fields an embedding template can reference as `{field}`
+const TEMPLATE_FIELDS: &[&str] = &[
+    "name",
+    "full_name",
+    "owner",
+    "language",
+    "description",
+    "topics",
+    "homepage",
+    "readme",
+    "notes",
+];
+
+// Characters of a README kept for embedding
+const README_EXCERPT_CHARS: usize = 1000;
+
+// Split a template into literal text and `{field}` references
+fn parse_template(template: &str) -> Result<Vec<(&str, Option<&str>)>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("Unclosed '{{' in embedding_template: {}", template))?;
+        let field = &rest[start + 1..end];
+        if !TEMPLATE_FIELDS.contains(&field) {
+            return Err(anyhow!(
+                "Unknown field {{{}}} in embedding_template. Available fields: {}",
+                field,
+                TEMPLATE_FIELDS.join(", ")
+            ));
+        }
+
+        parts.push((&rest[..start], Some(field)));
+        rest = &rest[end + 1..];
+    }
+    parts.push((rest, None));
+
+    Ok(parts)
+}
+
+// The validated embedding template of the profile
+fn embedding_template() -> Result<String> {
+    let template = settings()
+        .profile
+        .embedding_template
+        .clone()
+        .unwrap_or_else(|| DEFAULT_EMBEDDING_TEMPLATE.to_string());
+    parse_template(&template)?;
+    Ok(template)
+}
+
+fn template_uses(template: &str, field: &str) -> bool {
+    template.contains(&format!("{{{}}}", field))
+}
+
+// Whether the vectors were made with another template than the configured one
+fn template_is_stale(conn: &Connection) -> Result<bool> {
+    Ok(
+        get_meta(conn, "embedding_template")?.as_deref() != Some(embedding_template()?.as_str())
+            && count(conn, "SELECT COUNT(*) FROM repo_vectors")? > 0,
+    )
+}
+
+// README excerpts and personal notes, repo text that isn't part of the GitHub payload
+#[derive(Default)]
+struct RepoExtras {
+    readmes: HashMap<u64, String>,
+    notes: HashMap<u64, String>,
+}
+
+// Load the extras the template refers to
+fn load_repo_extras(conn: &Connection, template: &str) -> Result<RepoExtras> {
+    let load = |sql: &str| -> Result<HashMap<u64, String>> {
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(rows)
+    };
+
+    let mut extras = RepoExtras::default();
+    if template_uses(template, "readme") {
+        extras.readmes = load("SELECT repo_id, excerpt FROM repo_readmes")?;
+    }
+    if template_uses(template, "notes") {
+        extras.notes = load("SELECT repo_id, note FROM repo_notes")?;
+    }
+    Ok(extras)
+}
+
+// Create text for embedding by filling in the template
+fn embedding_text(template: &str, repo: &StarredRepo, extras: &RepoExtras) -> String {
+    let mut text = String::new();
+
+    // Templates are validated when loaded, so parsing can't fail here
+    for (literal, field) in parse_template(template).unwrap_or_default() {
+        text.push_str(literal);
+        let value = match field {
+            Some("name") => repo.name.clone(),
+            Some("full_name") => repo.full_name.clone(),
+            Some("owner") => repo.owner.login.clone(),
+            Some("language") => repo.language.clone().unwrap_or_default(),
+            Some("description") => repo.description.clone().unwrap_or_default(),
+            Some("topics") => repo.topics.join(" "),
+            Some("homepage") => repo.homepage.clone().unwrap_or_default(),
+            Some("readme") => extras.readmes.get(&repo.id).cloned().unwrap_or_default(),
+            Some("notes") => extras.notes.get(&repo.id).cloned().unwrap_or_default(),
+            _ => String::new(),
+        };
+        text.push_str(&value);
+    }
+
+    // Collapse the gaps left by empty fields
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Identifies the input of a vector: the embedded text and the model that embedded it
+fn embedding_hash(model: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Input hashes of the stored vectors by repo id
+fn vector_hashes(conn: &Connection) -> Result<HashMap<i64, String>> {
+    let mut stmt = conn.prepare(
+        "SELECT repo_id, hash FROM repo_vector_hashes
+        WHERE repo_id IN (SELECT rowid FROM repo_vectors)",
+    )?;
+    let hashes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(hashes)
+}
+
+// Query embeddings cached when the profile doesn't set query_cache_size
+const DEFAULT_QUERY_CACHE_SIZE: usize = 1000;
+
+// Cache key of a query. The normalized text is also what gets embedded, so
+// queries differing only in spacing share a vector.
+fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Embedding of a search query as little-endian f32 bytes, from the cache
+// when the query was embedded with the same model before
+fn query_embedding(conn: &Connection, spec: &ModelSpec, query: &str) -> Result<Vec<u8>> {
+    let query = normalize_query(query);
+    let cache_size = settings()
+        .profile
+        .query_cache_size
+        .unwrap_or(DEFAULT_QUERY_CACHE_SIZE);
+
+    if cache_size > 0 {
+        let cached: Option<Vec<u8>> = match conn.query_row(
+            "SELECT embedding FROM query_embeddings WHERE model = ? AND query = ?",
+            params![spec.name, query],
+            |row| row.get(0),
+        ) {
+            Ok(embedding) => Some(embedding),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        // A server model can change its dimension under the same name
+        if let Some(embedding) = cached.filter(|e| e.len() == spec.dimension * 4) {
+            // Updating the recency is best effort, like storing below
+            touch_query_embedding(conn, &spec.name, &query).ok();
+            return Ok(embedding);
+        }
+    }
+
+    let mut embedder = create_embedder(spec)?;
+    let embedding: Vec<u8> = embedder
+        .embed(&[&query])
+        .context("Embedding query failed")?[0]
+        .iter()
+        .flat_map(|&f| f.to_le_bytes())
+        .collect();
+
+    // A read-only or busy database shouldn't fail the search
+    if cache_size > 0 {
+        cache_query_embedding(conn, &spec.name, &query, &embedding, cache_size).ok();
+    }
+
+    Ok(embedding)
+}
+
+// Mark a cached query as the most recently used. last_used is a counter
+// rather than a timestamp, so the order is exact within a second.
+fn touch_query_embedding(conn: &Connection, model: &str, query: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE query_embeddings
+        SET last_used = (SELECT MAX(last_used) + 1 FROM query_embeddings)
+        WHERE model = ? AND query = ?",
+        params![model, query],
+    )?;
+    Ok(())
+}
+
+// Store a query embedding and evict the least recently used ones beyond the cache size
+fn cache_query_embedding(
+    conn: &Connection,
+    model: &str,
+    query: &str,
+    embedding: &[u8],
+    cache_size: usize,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO query_embeddings (model, query, embedding, last_used)
+        VALUES (?, ?, ?, (SELECT COALESCE(MAX(last_used), 0) + 1 FROM query_embeddings))",
+        params![model, query, embedding],
+    )?;
+    conn.execute(
+        "DELETE FROM query_embeddings WHERE rowid IN (
+            SELECT rowid FROM query_embeddings ORDER BY last_used DESC LIMIT -1 OFFSET ?
+        )",
+        params![cache_size as i64],
+    )?;
+    Ok(())
+}
+
+// Replace the embedding of a repo, vec0 tables don't support upserts
+fn store_embedding(conn: &Connection, repo_id: i64, embedding: &[f32], hash: &str) -> Result<()> {
+    // Convert f32 vector to bytes for SQLite (safe version)
+    let embedding_bytes: Vec<u8> = embedding.iter().flat_map(|&f| f.to_le_bytes()).collect();
+
+    conn.execute("DELETE FROM repo_vectors WHERE rowid = ?", params![repo_id])?;
+    insert_vector(
+        conn,
+        "repo_vectors",
+        vector_storage(conn)?,
+        repo_id,
+        &embedding_bytes,
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO repo_vector_hashes (repo_id, hash) VALUES (?, ?)",
+        params![repo_id, hash],
+    )?;
+
+    Ok(())
+}
+
+// Store repositories and their embeddings in the database
+fn store_repos_in_db(username: &str, stars: &[Star], timestamp: i64) -> Result<()> {
+    // Create a progress bar for the embedding process
+    let progress = progress_bar(stars.len() as u64, "Processing repositories");
+
+    let mut conn = init_db()?;
+
+    // In keyword-only mode the repos are stored without vectors
+    let semantic = semantic_enabled();
+    let spec = if semantic {
+        // Refuse before embedding anything if the vectors are from another model
+        let spec = configured_model(&conn)?;
+        ensure_vector_model(&conn, &spec)?;
+        Some(spec)
+    } else {
+        None
+    };
+
+    // Only repos whose embedding text changed since their vector was made need
+    // a new one, so the model isn't even loaded when nothing changed
+    let template = embedding_template()?;
+    let mut changed = Vec::new();
+    let mut texts = Vec::new();
+    if let Some(spec) = &spec {
+        let extras = load_repo_extras(&conn, &template)?;
+        let stored_hashes = vector_hashes(&conn)?;
+        for star in stars {
+            let text = embedding_text(&template, &star.repo, &extras);
+            let hash = embedding_hash(&spec.name, &text);
+            let repo_id = u64_to_sqlite(star.repo.id, "repo.id")?;
+            if stored_hashes.get(&repo_id) != Some(&hash) {
+                changed.push((repo_id, hash));
+                texts.push(text);
+            }
+        }
+    }
+
+    // Generate the embeddings before opening the write transaction, so the
+    // database isn't locked for the minutes embedding can take
+    progress.set_length(texts.len() as u64);
+    let embeddings = match &spec {
+        Some(spec) if !texts.is_empty() => {
+            let mut embedder = create_embedder(spec)?;
+            embed_texts(embedder.as_mut(), texts, &progress)?
+        }
+        _ => Vec::new(),
+    };
+
+    // Begin transaction, taking the write lock up front so it can wait for
+    // other writers instead of failing when upgrading from a read
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    if let Some(spec) = &spec {
+        // Check again in case another process changed the model while we were embedding
+        ensure_vector_model(&tx, spec)?;
+
+        // Into an empty table every vector is made with the current template
+        if count(&tx, "SELECT COUNT(*) FROM repo_vectors")? == 0 {
+            set_meta(&tx, "embedding_template", &template)?;
+        }
+    }
+
+    // Update or insert user
+    tx.execute(
+        "INSERT OR REPLACE INTO users (username, last_updated) VALUES (?, ?)",
+        params![username, timestamp],
+    )?;
+
+    // Replace this user's stars, repos shared with other users stay in place
+    tx.execute(
+        "DELETE FROM user_stars WHERE username = ?",
+        params![username],
+    )?;
+
+    let upsert_sql = upsert_repo_sql();
+    let mut upsert = tx.prepare(&upsert_sql)?;
+
+    for star in stars {
+        let repo_id = u64_to_sqlite(star.repo.id, "repo.id")?;
+
+        // Insert or refresh repo data
+        upsert.execute(params![repo_id, star.payload.to_string()])?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO user_stars (username, repo_id, starred_at) VALUES (?, ?, ?)",
+            params![username, repo_id, star.starred_at],
+        )?;
+    }
+
+    drop(upsert);
+
+    for ((repo_id, hash), embedding) in changed.iter().zip(&embeddings) {
+        store_embedding(&tx, *repo_id, embedding, hash)?;
+    }
+
+    // Drop repos (and vectors) that were only starred by this user and got unstarred
+    collect_garbage(&tx)?;
+
+    tx.commit()?;
+    progress.finish_with_message(format!(
+        "Repositories stored in database, {} of {} embedded",
+        changed.len(),
+        stars.len()
+    ));
+
+    if !semantic {
+        println!(
+            "Stored the repositories without vectors (keyword-only mode). Run 'gh-stars reembed' to embed them for semantic search."
+        );
+    } else if template_is_stale(&conn)? {
+        println!(
+            "The embedding template changed since the other repositories were embedded. Run 'gh-stars reembed' to update their vectors."
+        );
+    }
+
+    Ok(())
+}
+
+// Build the filter restricting results to repos starred by any of the given users
+fn user_filter_sql(usernames: &[String]) -> String {
+    let placeholders: Vec<String> = (0..usernames.len()).map(|_| "?".to_string()).collect();
+    format!(
+        "r.id IN (SELECT repo_id FROM user_stars WHERE username IN ({}))",
+        placeholders.join(",")
+    )
+}
+
+// Build the `AND r.language IN (...)` clause for an optional language filter
+fn language_filter_sql(languages: &Option<Vec<String>>) -> String {
+    match languages {
+        Some(langs) if !langs.is_empty() => {
+            let placeholders: Vec<String> = (0..langs.len()).map(|_| "?".to_string()).collect();
+            format!(" AND r.language IN ({})", placeholders.join(","))
+        }
+        _ => String::new(),
+    }
+}
+
+// Parameters for `user_filter_sql` followed by `language_filter_sql`
+fn filter_params<'a>(
+    usernames: &'a [String],
+    languages: &'a Option<Vec<String>>,
+) -> Vec<&'a dyn rusqlite::ToSql> {
+    let mut params: Vec<&dyn rusqlite::ToSql> = Vec::new();
+
+    for username in usernames {
+        params.push(username as &dyn rusqlite::ToSql);
+    }
+
+    if let Some(langs) = languages {
+        for lang in langs {
+            params.push(lang as &dyn rusqlite::ToSql);
+        }
+    }
+
+    params
+}
+
+// Keys of the search qualifiers, other `key:value` words are free text
+const QUALIFIERS: &[&str] = &[
+    "lang", "language", "stars", "owner", "topic", "user", "pushed", "archived",
+];
+
+// A search query split into free text, ranked by keyword and semantic search,
+// and qualifiers such as `lang:rust` or `stars:>500`, turned into SQL filters
+#[derive(Debug, Default)]
+struct SearchQuery {
+    text: String,
+    // Users from user: qualifiers, replacing --username
+    users: Vec<String>,
+    // Conditions on the repo `r` that must all hold
+    filters: Vec<String>,
+    params: Vec<rusqlite::types::Value>,
+}
+
+impl SearchQuery {
+    fn parse(input: &str) -> Result<SearchQuery> {
+        let mut query = SearchQuery::default();
+        let mut words = Vec::new();
+        let mut languages = Vec::new();
+        let mut owners = Vec::new();
+
+        for (token, quoted, negated) in query_tokens(input)? {
+            let qualifier = if quoted {
+                None
+            } else {
+                token
+                    .split_once(':')
+                    .filter(|(key, _)| QUALIFIERS.contains(&key.to_ascii_lowercase().as_str()))
+            };
+
+            match qualifier {
+                // -archived is a filter, not a word to exclude
+                None if negated && !quoted && token.eq_ignore_ascii_case("archived") => {
+                    query.filters.push("r.archived = 0".to_string());
+                }
+                // Phrases must match exactly and words excluded with - must not
+                // match at all, in keyword and semantic results alike
+                None if quoted || negated => {
+                    let phrase = fts_phrase(&token).ok_or_else(|| {
+                        anyhow!(
+                            "'{}{}' has no words to match",
+                            if negated { "-" } else { "" },
+                            token
+                        )
+                    })?;
+                    query.filters.push(format!(
+                        "r.id {}IN (SELECT rowid FROM repos_fts WHERE repos_fts MATCH ?)",
+                        if negated { "NOT " } else { "" }
+                    ));
+                    query.params.push(phrase.into());
+                    if !negated {
+                        words.push(token);
+                    }
+                }
+                None => words.push(token),
+                Some((key, value)) => {
+                    let key = key.to_ascii_lowercase();
+                    if value.is_empty() {
+                        return Err(anyhow!("'{}:' needs a value", key));
+                    }
+                    if negated && !matches!(key.as_str(), "lang" | "language" | "owner" | "topic") {
+                        return Err(anyhow!(
+                            "'-{}:' isn't supported, only lang:, owner: and topic: can be negated",
+                            key
+                        ));
+                    }
+
+                    let values: Vec<String> = value
+                        .split(',')
+                        .map(|v| v.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                        .collect();
+                    match key.as_str() {
+                        // A repo has one language and owner, so repeated
+                        // qualifiers match any of the values
+                        "lang" | "language" if !negated => languages.extend(values),
+                        "owner" if !negated => owners.extend(values),
+                        "lang" | "language" | "owner" => {
+                            let column = if key == "owner" { "owner" } else { "language" };
+                            query.filters.push(format!(
+                                "COALESCE(lower(r.{}), '') NOT IN ({})",
+                                column,
+                                placeholders(values.len())
+                            ));
+                            query
+                                .params
+                                .extend(values.into_iter().map(|v| v.to_lowercase().into()));
+                        }
+                        "topic" => {
+                            for topic in values {
+                                // topics holds the topics separated by spaces
+                                query.filters.push(format!(
+                                    "instr(' ' || lower(COALESCE(r.topics, '')) || ' ', ?) {} 0",
+                                    if negated { "=" } else { ">" }
+                                ));
+                                query
+                                    .params
+                                    .push(format!(" {} ", topic.to_lowercase()).into());
+                            }
+                        }
+                        "user" => query.users.extend(values),
+                        "stars" => query.push_range("r.stars", value, |bound| {
+                            bound.parse::<i64>().map(Into::into).map_err(|_| {
+                                anyhow!("'{}' in stars:{} is not a number", bound, value)
+                            })
+                        })?,
+                        "pushed" => query.push_range("date(r.pushed_at)", value, |bound| {
+                            parse_date(bound).map(Into::into).ok_or_else(|| {
+                                anyhow!(
+                                    "'{}' in pushed:{} is not a date like 2024-01-31",
+                                    bound,
+                                    value
+                                )
+                            })
+                        })?,
+                        "archived" => {
+                            let archived = match value.to_ascii_lowercase().as_str() {
+                                "true" => 1,
+                                "false" => 0,
+                                _ => {
+                                    return Err(anyhow!(
+                                        "archived: must be true or false, not '{}'",
+                                        value
+                                    ));
+                                }
+                            };
+                            query.filters.push(format!("r.archived = {}", archived));
+                        }
+                        _ => unreachable!("{} is not in QUALIFIERS", key),
+                    }
+                }
+            }
+        }
+
+        for (column, values) in [("language", languages), ("owner", owners)] {
+            if !values.is_empty() {
+                query.filters.push(format!(
+                    "lower(r.{}) IN ({})",
+                    column,
+                    placeholders(values.len())
+                ));
+                query
+                    .params
+                    .extend(values.into_iter().map(|v| v.to_lowercase().into()));
+            }
+        }
+
+        query.text = words.join(" ");
+        Ok(query)
+    }
+
+    // Plain text without qualifiers, as used by prune --query
+    fn text(text: &str) -> SearchQuery {
+        SearchQuery {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Add a filter for a range like `>500`, `<=10`, `10..100`, `10..*` or an exact value
+    fn push_range(
+        &mut self,
+        column: &str,
+        range: &str,
+        parse: impl Fn(&str) -> Result<rusqlite::types::Value>,
+    ) -> Result<()> {
+        let mut bounds = Vec::new();
+        if let Some((low, high)) = range.split_once("..") {
+            if low != "*" {
+                bounds.push((">=", low));
+            }
+            if high != "*" {
+                bounds.push(("<=", high));
+            }
+        } else {
+            let (op, bound) = [">=", "<=", ">", "<"]
+                .into_iter()
+                .find_map(|op| range.strip_prefix(op).map(|bound| (op, bound)))
+                .unwrap_or(("=", range));
+            bounds.push((op, bound));
+        }
+
+        for (op, bound) in bounds {
+            self.filters.push(format!("{} {} ?", column, op));
+            self.params.push(parse(bound)?);
+        }
+        Ok(())
+    }
+
+    // ` AND ...` for every filter, to append to a WHERE clause
+    fn filter_sql(&self) -> String {
+        self.filters.iter().map(|f| format!(" AND {}", f)).collect()
+    }
+}
+
+// Split a query into (text, quoted, negated) tokens at whitespace outside of
+// double quotes. A token is quoted when it starts with a quote, after an
+// optional `-`.
+fn query_tokens(input: &str) -> Result<Vec<(String, bool, bool)>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+        let quoted = chars.peek() == Some(&'"');
+
+        let mut token = String::new();
+        let mut in_quotes = false;
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() && !in_quotes {
+                break;
+            }
+            chars.next();
+            if c == '"' {
+                in_quotes = !in_quotes;
+            } else {
+                token.push(c);
+            }
+        }
+
+        if in_quotes {
+            return Err(anyhow!("Unterminated quote in '{}'", input.trim()));
+        }
+        if token.is_empty() {
+            if negated && !quoted {
+                return Err(anyhow!("'-' must be followed by a word to exclude"));
+            }
+            continue;
+        }
+        tokens.push((token, quoted, negated));
+    }
+
+    Ok(tokens)
+}
+
+// FTS5 query matching the words of `text` next to each other, in order
+fn fts_phrase(text: &str) -> Option<String> {
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(format!("\"{}\"", words.join(" ")))
+    }
+}
+
+// Validate a YYYY-MM-DD date
+fn parse_date(s: &str) -> Option<String> {
+    let mut parts = s.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    let valid = year.len() == 4
+        && month.len() == 2
+        && day.len() == 2
+        && [year, month, day]
+            .iter()
+            .all(|part| part.chars().all(|c| c.is_ascii_digit()))
+        && (1..=12).contains(&month.parse::<u32>().ok()?)
+        && (1..=31).contains(&day.parse::<u32>().ok()?);
+    valid.then(|| s.to_string())
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+// Turn free text into an FTS5 query that requires every word, matching words
+// as prefixes. Punctuation is dropped so user input can't inject FTS5 syntax.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+// Keyword search over the full-text index, best BM25 matches first
+fn keyword_search(
+    conn: &Connection,
+    usernames: &[String],
+    languages: &Option<Vec<String>>,
+    query: &SearchQuery,
+    limit: usize,
+) -> Result<Vec<(StarredRepo, f64)>> {
+    let Some(match_query) = fts_query(&query.text) else {
+        return Ok(Vec::new());
+    };
+
+    // Column weights for name, full_name, description, topics and owner.
+    // bm25() is lower for better matches, so negate it into a score.
+    let keyword_sql = format!(
+        "SELECT r.*, {},
+        -bm25(repos_fts, 10.0, 5.0, 2.0, 4.0, 3.0) AS score
+        FROM repos_fts
+        JOIN repos r ON r.id = repos_fts.rowid
+        WHERE repos_fts MATCH ?
+        AND {}{}{}
+        ORDER BY score DESC, r.stars DESC
+        LIMIT {}",
+        STARRED_BY_SQL,
+        user_filter_sql(usernames),
+        language_filter_sql(languages),
+        query.filter_sql(),
+        limit
+    );
+
+    let mut keyword_params: Vec<&dyn rusqlite::ToSql> = vec![&match_query as &dyn rusqlite::ToSql];
+
+    // Add username, language and qualifier parameters
+    keyword_params.extend(filter_params(usernames, languages));
+    keyword_params.extend(query.params.iter().map(|p| p as &dyn rusqlite::ToSql));
+
+    let mut keyword_stmt = conn.prepare(&keyword_sql)?;
+
+    let keyword_rows =
+        keyword_stmt.query_map(rusqlite::params_from_iter(keyword_params.iter()), |row| {
+            let score: f64 = row.get("score")?;
+            Ok((repo_from_row(row)?, score))
+        })?;
+
+    let mut results = Vec::new();
+    for row_result in keyword_rows {
+        results.push(row_result?);
+    }
+
+    Ok(results)
+}
+
+// How search ranks its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SearchMode {
+    /// Full-text matches only
+    Keyword,
+    /// Nearest vectors only
+    Semantic,
+    /// Both, fused by rank
+    Hybrid,
+}
+
+// Semantic share of hybrid ranking when neither --semantic-weight nor the config set it
+const DEFAULT_SEMANTIC_WEIGHT: f64 = 0.5;
+
+// Damping of reciprocal rank fusion. Larger values flatten the gap between
+// the top ranks and the rest, 60 is the value from the original paper.
+const RRF_K: f64 = 60.0;
+
+// The search mode from --mode, then the profile. Hybrid search falls back to
+// keywords in keyword-only mode, explicit semantic search fails there.
+fn configured_search_mode(mode: Option<SearchMode>) -> Result<SearchMode> {
+    let mode = match (mode, settings().profile.search_mode.as_deref()) {
+        (Some(mode), _) => mode,
+        (None, Some(name)) => SearchMode::from_str(name, true).map_err(|_| {
+            anyhow!(
+                "Unknown search mode '{}'. Use keyword, semantic or hybrid.",
+                name
+            )
+        })?,
+        (None, None) => SearchMode::Hybrid,
+    };
+
+    if mode == SearchMode::Semantic && !semantic_enabled() {
+        return Err(anyhow!(
+            "Semantic search is off in keyword-only mode. Use --mode keyword, or enable it with semantic = true."
+        ));
+    }
+    Ok(mode)
+}
+
+// The semantic weight from --semantic-weight, then the profile
+fn configured_semantic_weight(weight: Option<f64>) -> Result<f64> {
+    match weight.or(settings().profile.semantic_weight) {
+        Some(weight) if !(0.0..=1.0).contains(&weight) => Err(anyhow!(
+            "semantic_weight must be between 0 and 1, got {}",
+            weight
+        )),
+        weight => Ok(weight.unwrap_or(DEFAULT_SEMANTIC_WEIGHT)),
+    }
+}
+
+// Merge ranked result lists with weighted reciprocal rank fusion: a repo
+// scores weight / (RRF_K + rank) in each list it appears in. Ranks are
+// comparable where BM25 scores and vector distances are not.
+fn fuse_rankings(lists: Vec<(Vec<(StarredRepo, f64)>, f64)>) -> Vec<StarredRepo> {
+    let mut fused: Vec<(StarredRepo, f64)> = Vec::new();
+    let mut positions: HashMap<u64, usize> = HashMap::new();
+
+    for (results, weight) in lists {
+        for (rank, (repo, _)) in results.into_iter().enumerate() {
+            let score = weight / (RRF_K + rank as f64 + 1.0);
+            match positions.get(&repo.id) {
+                Some(&position) => fused[position].1 += score,
+                None => {
+                    positions.insert(repo.id, fused.len());
+                    fused.push((repo, score));
+                }
+            }
+        }
+    }
+
+    // Ties, for example with a weight of 0, go to the more popular repo
+    fused.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then_with(|| b.0.stargazers_count.cmp(&a.0.stargazers_count))
+    });
+
+    fused.into_iter().map(|(repo, _)| repo).collect()
+}
+
+// Combined search function that ranks by keyword matches, semantic
+// similarity or both
+fn search_repos(
+    usernames: &[String],
+    languages: &Option<Vec<String>>,
+    query: &SearchQuery,
+    limit: usize,
+    mode: SearchMode,
+    semantic_weight: f64,
+) -> Result<Vec<StarredRepo>> {
+    let conn = init_db()?;
+
+    // Without free text, just list the repos matching the filters
+    if query.text.is_empty() {
+        let sql = format!(
+            "SELECT r.json, {}
+            FROM repos r
+            WHERE {}{}{}
+            ORDER BY r.stars DESC
+            LIMIT {}",
+            STARRED_BY_SQL,
+            user_filter_sql(usernames),
+            language_filter_sql(languages),
+            query.filter_sql(),
+            limit
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+
+        let mut params = filter_params(usernames, languages);
+        params.extend(query.params.iter().map(|p| p as &dyn rusqlite::ToSql));
+        let repos_iter =
+            stmt.query_map(rusqlite::params_from_iter(params.iter()), repo_from_row)?;
+
+        let mut repos = Vec::new();
+        for repo in repos_iter {
+            repos.push(repo?);
+        }
+
+        return Ok(repos);
+    }
+
+    // Hybrid search with all the weight on one side is that search alone, so
+    // a weight of 0 never loads the model. It also keeps to keywords for
+    // queries too short to embed meaningfully.
+    let mode = match mode {
+        SearchMode::Hybrid
+            if semantic_weight == 0.0 || !semantic_enabled() || query.text.len() < 3 =>
+        {
+            SearchMode::Keyword
+        }
+        SearchMode::Hybrid if semantic_weight == 1.0 => SearchMode::Semantic,
+        mode => mode,
+    };
+    let (keyword_weight, semantic_weight) = match mode {
+        SearchMode::Keyword => (1.0, 0.0),
+        SearchMode::Semantic => (0.0, 1.0),
+        SearchMode::Hybrid => (1.0 - semantic_weight, semantic_weight),
+    };
+
+    let keyword = if keyword_weight > 0.0 {
+        keyword_search(&conn, usernames, languages, query, limit)?
+    } else {
+        Vec::new()
+    };
+    let semantic = if semantic_weight > 0.0 {
+        vector_search(&conn, usernames, languages, query, limit)?
+    } else {
+        Vec::new()
+    };
+
+    let mut results = fuse_rankings(vec![(keyword, keyword_weight), (semantic, semantic_weight)]);
+    results.truncate(limit);
+
+    Ok(results)
+}
+
+// Semantic search over the repo vectors, nearest first, scored by similarity
+fn vector_search(
+    conn: &Connection,
+    usernames: &[String],
+    languages: &Option<Vec<String>>,
+    query: &SearchQuery,
+    limit: usize,
+) -> Result<Vec<(StarredRepo, f64)>> {
+    // Query embeddings are only comparable with vectors of the same model
+    let spec = configured_model(conn)?;
+    if stored_model(conn)? != Some((spec.name.clone(), spec.dimension))
+        && count(conn, "SELECT COUNT(*) FROM repo_vectors")? > 0
+    {
+        return Err(model_mismatch_error(conn, &spec));
+    }
+
+    if template_is_stale(conn)? {
+        eprintln!(
+            "Warning: the repositories were embedded with a different embedding template. Run 'gh-stars reembed' to update them."
+        );
+    }
+
+    let query_embedding_bytes = query_embedding(conn, &spec, &query.text)?;
+
+    // Filter inside the KNN rather than after it, where the nearest vectors
+    // of other users' or languages' repos would take up the limit
+    let candidates = format!(
+        "SELECT r.id FROM repos r WHERE {}{}{}",
+        user_filter_sql(usernames),
+        language_filter_sql(languages),
+        query.filter_sql()
+    );
+    let vector_sql = format!(
+        "SELECT r.*, {}, v.distance AS score
+        FROM repos r
+        JOIN ({}) v ON r.id = v.rowid
+        ORDER BY v.distance ASC",
+        STARRED_BY_SQL,
+        knn_sql(
+            "repo_vectors",
+            vector_storage(conn)?,
+            limit,
+            Some(&candidates)
+        )
+    );
+
+    // Build vector search parameters without cloning
+    let mut vector_params: Vec<&dyn rusqlite::ToSql> = Vec::new();
+
+    // Add embedding parameter
+    vector_params.push(&query_embedding_bytes as &dyn rusqlite::ToSql);
+
+    // Add username, language and qualifier parameters
+    vector_params.extend(filter_params(usernames, languages));
+    vector_params.extend(query.params.iter().map(|p| p as &dyn rusqlite::ToSql));
+
+    let mut vector_stmt = conn.prepare(&vector_sql)?;
+
+    let vector_rows =
+        vector_stmt.query_map(rusqlite::params_from_iter(vector_params.iter()), |row| {
+            let score: f64 = row.get("score")?;
+            // Convert distance into a similarity for ranking
+            Ok((repo_from_row(row)?, 1.0 - score))
+        })?;
+
+    let mut results = Vec::new();
+    for row_result in vector_rows {
+        results.push(row_result?);
+    }
+
+    Ok(results)
+}
+
+// Rules used by the prune command to select stars, all given rules must match
+struct PruneRules {
+    archived: bool,
+    stale_years: Option<u64>,
+    forks: bool,
+    languages: Option<Vec<String>>,
+    query: Option<String>,
+}
+
+impl PruneRules {
+    fn is_empty(&self) -> bool {
+        !self.archived
+            && self.stale_years.is_none()
+            && !self.forks
+            && self.languages.as_ref().is_none_or(|langs| langs.is_empty())
+            && self.query.as_ref().is_none_or(|query| query.is_empty())
+    }
+}
+
+// Convert days since the Unix epoch into a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Date `years` years ago in the YYYY-MM-DD form GitHub timestamps start with
+fn date_years_ago(years: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (year, month, day) = civil_from_days(now / 86400);
+    format!("{:04}-{:02}-{:02}", year - years as i64, month, day)
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Select the cached stars of a user that match all of the prune rules
+fn select_prune_candidates(username: &str, rules: &PruneRules) -> Result<Vec<StarredRepo>> {
+    let conn = init_db()?;
+    let usernames = [username.to_string()];
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT r.json, {}
+        FROM repos r
+        JOIN user_stars us ON us.repo_id = r.id
+        WHERE us.username = ?
+        ORDER BY r.stars DESC",
+        STARRED_BY_SQL
+    ))?;
+    let repos_iter = stmt.query_map(params![username], repo_from_row)?;
+
+    let mut repos = Vec::new();
+    for repo in repos_iter {
+        repos.push(repo?);
+    }
+
+    if repos.is_empty() {
+        return Err(anyhow!(
+            "No cached stars found for {}. Fetch stars for the user first using the fetch command.",
+            username
+        ));
+    }
+
+    // Stars cached before these fields were stored would silently never match
+    let mut fields = Vec::new();
+    if rules.archived {
+        fields.push("archived");
+    }
+    if rules.forks {
+        fields.push("fork");
+    }
+    if rules.stale_years.is_some() {
+        fields.push("pushed_at");
+    }
+    if !fields.is_empty() {
+        let missing: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM repos r
+                JOIN user_stars us ON us.repo_id = r.id
+                WHERE us.username = ? AND ({})",
+                fields
+                    .iter()
+                    .map(|field| format!("json_type(r.json, '$.{}') IS NULL", field))
+                    .collect::<Vec<_>>()
+                    .join(" OR ")
+            ),
+            params![username],
+            |row| row.get(0),
+        )?;
+        if missing > 0 {
+            return Err(anyhow!(
+                "{} of {}'s cached stars were fetched without {}, so the prune rules can't judge them. Run 'gh-stars fetch {} --force' first.",
+                missing,
+                username,
+                fields.join(", "),
+                username
+            ));
+        }
+    }
+
+    // Reuse keyword search so --query selects exactly what `search` would match
+    let query_matches: Option<HashSet<u64>> = match rules.query.as_deref() {
+        Some(query) if !query.is_empty() => Some(
+            keyword_search(
+                &conn,
+                &usernames,
+                &None,
+                &SearchQuery::text(query),
+                repos.len(),
+            )?
+            .into_iter()
+            .map(|(repo, _)| repo.id)
+            .collect(),
+        ),
+        _ => None,
+    };
+
+    let cutoff = rules.stale_years.map(date_years_ago);
+    let languages = rules.languages.as_ref().filter(|langs| !langs.is_empty());
+
+    Ok(repos
+        .into_iter()
+        .filter(|repo| {
+            (!rules.archived || repo.archived)
+                && (!rules.forks || repo.fork)
+                && cutoff.as_ref().is_none_or(|cutoff| {
+                    repo.pushed_at
+                        .as_ref()
+                        .is_some_and(|pushed_at| pushed_at.as_str() < cutoff.as_str())
+                })
+                && languages.is_none_or(|langs| {
+                    repo.language
+                        .as_ref()
+                        .is_some_and(|lang| langs.iter().any(|l| l.eq_ignore_ascii_case(lang)))
+                })
+                && query_matches
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&repo.id))
+        })
+        .collect())
+}
+
+// Remove a star from the cache, dropping the repo once no cached user stars it
+fn remove_cached_star(conn: &Connection, username: &str, repo_id: u64) -> Result<()> {
+    let repo_id = u64_to_sqlite(repo_id, "repo.id")?;
+
+    conn.execute(
+        "DELETE FROM user_stars WHERE username = ? AND repo_id = ?",
+        params![username, repo_id],
+    )?;
+
+    let still_starred: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM user_stars WHERE repo_id = ?)",
+        params![repo_id],
+        |row| row.get(0),
+    )?;
+
+    if !still_starred {
+        conn.execute("DELETE FROM repo_vectors WHERE rowid = ?", params![repo_id])?;
+        conn.execute("DELETE FROM repos WHERE id = ?", params![repo_id])?;
+    }
+
+    Ok(())
+}
+
+async fn prune_stars(
+    username: &str,
+    rules: &PruneRules,
+    dry_run: bool,
+    yes: bool,
+    token: &Option<String>,
+) -> Result<()> {
+    if rules.is_empty() {
+        return Err(anyhow!(
+            "No prune rules given. Use --archived, --stale-years, --forks, --language or --query."
+        ));
+    }
+
+    let selected = select_prune_candidates(username, rules)?;
+    display_repos(&selected);
+
+    if selected.is_empty() {
+        return Ok(());
+    }
+
+    if dry_run {
+        println!(
+            "\nDry run: {} repositories would be unstarred for {}.",
+            selected.len(),
+            username
+        );
+        return Ok(());
+    }
+
+    let github_token = get_github_token(token)?
+        .ok_or_else(|| anyhow!("A GitHub token is required to unstar repositories"))?;
+    let client = reqwest::Client::new();
+    let headers = github_headers(Some(&github_token))?;
+
+    // The API only removes stars of the authenticated user
+    let login = get_authenticated_login(&client, &headers).await?;
+    if !login.eq_ignore_ascii_case(username) {
+        return Err(anyhow!(
+            "The GitHub token belongs to {}, stars of {} can only be removed with their own token",
+            login,
+            username
+        ));
+    }
+
+    if !yes
+        && !confirm(&format!(
+            "\nUnstar {} repositories for {}?",
+            selected.len(),
+            username
+        ))?
+    {
+        println!("Aborted, no repositories were unstarred.");
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let log_path = get_cache_dir()?.join(format!("pruned-{}-{}.txt", username, now));
+    let mut log = std::fs::File::create(&log_path)
+        .with_context(|| format!("Failed to create prune log {}", log_path.display()))?;
+
+    let conn = init_db()?;
+
+    let progress = progress_bar(selected.len() as u64, "");
+
+    let mut removed = 0;
+    for repo in &selected {
+        progress.set_message(format!("Unstarring {}", repo.full_name));
+
+        let url = format!("https://api.github.com/user/starred/{}", repo.full_name);
+        let response =
+            send_respecting_rate_limit(|| client.delete(&url).headers(headers.clone())).await?;
+
+        if response.status().is_success() {
+            // Log before touching the cache so the log always covers every removed star
+            writeln!(log, "{}", repo.full_name)?;
+            remove_cached_star(&conn, username, repo.id)?;
+            removed += 1;
+        } else {
+            progress.println(format!(
+                "Failed to unstar {}: {}",
+                repo.full_name,
+                response.status()
+            ));
+        }
+
+        progress.inc(1);
+
+        // Space out mutating requests to stay clear of GitHub's secondary rate limits
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    progress.finish_with_message(format!("Unstarred {} repositories", removed));
+    println!("Removed repositories were logged to {}", log_path.display());
+    println!(
+        "Use 'gh-stars restar {}' to star them again.",
+        log_path.display()
+    );
+
+    Ok(())
+}
+
+// Star every repository listed in a prune log again
+async fn restar_repos(log: &Path, token: &Option<String>) -> Result<()> {
+    let contents = std::fs::read_to_string(log)
+        .with_context(|| format!("Failed to read prune log {}", log.display()))?;
+    let repos: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let github_token = get_github_token(token)?
+        .ok_or_else(|| anyhow!("A GitHub token is required to star repositories"))?;
+    let client = reqwest::Client::new();
+    let headers = github_headers(Some(&github_token))?;
+
+    let progress = progress_bar(repos.len() as u64, "");
+
+    let mut starred = 0;
+    for full_name in &repos {
+        progress.set_message(format!("Starring {}", full_name));
+
+        let url = format!("https://api.github.com/user/starred/{}", full_name);
+        let response = send_respecting_rate_limit(|| {
+            client
+                .put(&url)
+                .headers(headers.clone())
+                .header(CONTENT_LENGTH, 0)
+        })
+        .await?;
+
+        if response.status().is_success() {
+            starred += 1;
+        } else {
+            progress.println(format!(
+                "Failed to star {}: {}",
+                full_name,
+                response.status()
+            ));
+        }
+
+        progress.inc(1);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    progress.finish_with_message(format!("Starred {} repositories", starred));
+    println!("Run 'gh-stars fetch <username> --force' to refresh the cache.");
+
+    Ok(())
+}
+
+// Gzip compressed files start with these two bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Path next to `path` for a file being written, renamed or removed once done
+fn temporary_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Read the vector dimension from the repo_vectors definition, e.g. `float[384]`
+fn vector_dimension(conn: &Connection) -> Result<Option<usize>> {
+    let sql: Option<String> = match conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE name = 'repo_vectors'",
+        [],
+        |row| row.get(0),
+    ) {
+        Ok(sql) => sql,
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(sql.and_then(|sql| {
+        let start = sql.find("float[")? + "float[".len();
+        let end = start + sql[start..].find(']')?;
+        sql[start..end].trim().parse().ok()
+    }))
+}
+
+// Write a consistent snapshot of the database using the SQLite online backup API
+fn backup_db(file: &Path, compress: bool) -> Result<()> {
+    let conn = init_db()?;
+    let compress = compress || file.extension().is_some_and(|ext| ext == "gz");
+
+    // Snapshot into a temporary file first so a failed backup never leaves a
+    // truncated file behind
+    let snapshot = temporary_path(file, ".tmp");
+    if snapshot.exists() {
+        std::fs::remove_file(&snapshot)?;
+    }
+    conn.backup(MAIN_DB, &snapshot, None)
+        .with_context(|| format!("Failed to back up database to {}", snapshot.display()))?;
+
+    // The snapshot inherits WAL mode, switch it back so it's a single self-contained file
+    Connection::open(&snapshot)?.query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(()))?;
+
+    if compress {
+        let mut input = std::fs::File::open(&snapshot)?;
+        let output = std::fs::File::create(file)
+            .with_context(|| format!("Failed to create {}", file.display()))?;
+        let mut encoder = GzEncoder::new(output, Compression::default());
+        std::io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?;
+        std::fs::remove_file(&snapshot)?;
+    } else {
+        std::fs::rename(&snapshot, file)?;
+    }
+
+    println!(
+        "Backed up {} to {} ({}{})",
+        get_db_path()?.display(),
+        file.display(),
+        format_size(std::fs::metadata(file)?.len()),
+        if compress { ", compressed" } else { "" }
+    );
+
+    Ok(())
+}
+
+// Replace the database with a snapshot written by `backup`
+fn restore_db(file: &Path, yes: bool) -> Result<()> {
+    let db_path = get_db_path()?;
+
+    let mut magic = [0u8; 2];
+    let mut input =
+        std::fs::File::open(file).with_context(|| format!("Failed to open {}", file.display()))?;
+    let compressed =
+        std::io::Read::read_exact(&mut input, &mut magic).is_ok() && magic == GZIP_MAGIC;
+
+    // Decompress next to the database so the snapshot can be opened by SQLite
+    let snapshot = if compressed {
+        let snapshot = temporary_path(&db_path, ".restore.tmp");
+        let mut decoder = GzDecoder::new(std::fs::File::open(file)?);
+        let mut output = std::fs::File::create(&snapshot)?;
+        std::io::copy(&mut decoder, &mut output)
+            .with_context(|| format!("Failed to decompress {}", file.display()))?;
+        Some(snapshot)
+    } else {
+        None
+    };
+    let source = snapshot.as_deref().unwrap_or(file);
+
+    let result = restore_snapshot(source, &db_path, yes);
+
+    if let Some(snapshot) = &snapshot {
+        std::fs::remove_file(snapshot)?;
+    }
+
+    result
+}
+
+fn restore_snapshot(source: &Path, db_path: &Path, yes: bool) -> Result<()> {
+    let (version, model, users, repos) = {
+        let conn = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let version = schema_version(&conn)
+            .with_context(|| format!("{} is not a SQLite database", source.display()))?;
+        if version == 0 {
+            return Err(anyhow!("{} is not a gh-stars backup", source.display()));
+        }
+        // Backups from before the model was recorded only tell the dimension
+        let model = if version >= 5 {
+            stored_model(&conn)?
+        } else {
+            vector_dimension(&conn)?.map(|dimension| ("an unknown model".to_string(), dimension))
+        };
+        let vectors = count(&conn, "SELECT COUNT(*) FROM repo_vectors")?;
+        (
+            version,
+            model.filter(|_| vectors > 0),
+            count(&conn, "SELECT COUNT(*) FROM users")?,
+            count(&conn, "SELECT COUNT(*) FROM repos")?,
+        )
+    };
+
+    if version > MIGRATIONS.len() {
+        return Err(anyhow!(
+            "The backup has schema version {}, but this version of gh-stars only supports up to {}. Upgrade gh-stars to restore it.",
+            version,
+            MIGRATIONS.len()
+        ));
+    }
+
+    // Without a model in the config the backup's model is adopted. Only the
+    // dimension is known for old backups, so a matching one is accepted.
+    let spec = match &settings().profile.embedding_model {
+        Some(name) => Some(model_spec(parse_configured_model(name)?, model.clone())?),
+        None => None,
+    };
+    if let Some((name, dimension)) = model
+        && let Some(spec) = spec
+        && (dimension != spec.dimension || (version >= 5 && name != spec.name))
+    {
+        return Err(anyhow!(
+            "The backup's vectors were made with {} ({} dimensions), but the configured embedding model is {} ({} dimensions). Set embedding_model to the backup's model in the config to restore it.",
+            name,
+            dimension,
+            spec.name,
+            spec.dimension
+        ));
+    }
+
+    let mut conn = init_db()?;
+    let current_users = count(&conn, "SELECT COUNT(*) FROM users")?;
+    if current_users > 0
+        && !yes
+        && !confirm(&format!(
+            "Replace {} ({} cached users) with the backup ({} users, {} repositories)?",
+            db_path.display(),
+            current_users,
+            users,
+            repos
+        ))?
+    {
+        println!("Aborted, the database was not changed.");
+        return Ok(());
+    }
+
+    // Copies the pages into the open database, so other connections see either
+    // the old or the restored contents
+    conn.restore(MAIN_DB, source, None::<fn(Progress)>)
+        .with_context(|| format!("Failed to restore {}", source.display()))?;
+    drop(conn);
+
+    // Upgrade a backup taken by an older version of gh-stars
+    init_db()?;
+
+    println!(
+        "Restored {} users and {} repositories into {}",
+        users,
+        repos,
+        db_path.display()
+    );
+
+    Ok(())
+}
+
+// Rebuild the typed columns and the full-text index from the stored GitHub payloads
+fn reindex_repos() -> Result<()> {
+    let mut conn = init_db()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let assignments: Vec<String> = DERIVED_COLUMNS
+        .iter()
+        .map(|(column, expr)| format!("{} = {}", column, expr))
+        .collect();
+    let updated = tx
+        .execute(&format!("UPDATE repos SET {}", assignments.join(", ")), [])
+        .context("Failed to rebuild columns from the stored payloads")?;
+
+    tx.execute("INSERT INTO repos_fts(repos_fts) VALUES ('rebuild')", [])?;
+    tx.commit()?;
+
+    println!(
+        "Reindexed {} repositories from their stored payloads",
+        updated
+    );
+    Ok(())
+}
+
+// Embed cached repos and store their vectors in one transaction, so searches
+// see either the old or the new vectors. Repos whose vector is up to date are
+// skipped unless `force` is set. `reset` drops every vector first, for a model
+// switch. `complete` means every repo was embedded with the current template,
+// which is then recorded as the template of the stored vectors. Returns the
+// number of repos embedded.
+fn embed_stored_repos(
+    conn: &mut Connection,
+    spec: &ModelSpec,
+    repos: &[StarredRepo],
+    reset: bool,
+    complete: bool,
+    force: bool,
+) -> Result<usize> {
+    let template = embedding_template()?;
+    let extras = load_repo_extras(conn, &template)?;
+    let stored_hashes = if reset || force {
+        HashMap::new()
+    } else {
+        vector_hashes(conn)?
+    };
+
+    let mut outdated = Vec::new();
+    let mut texts = Vec::new();
+    let mut hashes = Vec::new();
+    for repo in repos {
+        let text = embedding_text(&template, repo, &extras);
+        let hash = embedding_hash(&spec.name, &text);
+        let repo_id = u64_to_sqlite(repo.id, "repo.id")?;
+        if stored_hashes.get(&repo_id) != Some(&hash) {
+            outdated.push(repo_id);
+            texts.push(text);
+            hashes.push(hash);
+        }
+    }
+
+    let progress = progress_bar(texts.len() as u64, "Embedding repositories");
+
+    let embeddings = if texts.is_empty() {
+        Vec::new()
+    } else {
+        let mut embedder = create_embedder(spec)?;
+        embed_texts(embedder.as_mut(), texts, &progress)?
+    };
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if reset {
+        reset_vectors(&tx, spec)?;
+    } else {
+        ensure_vector_model(&tx, spec)?;
+    }
+
+    for ((repo_id, embedding), hash) in outdated.iter().zip(&embeddings).zip(&hashes) {
+        // Skip repos another process removed while we were embedding
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM repos WHERE id = ?)",
+            params![repo_id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            store_embedding(&tx, *repo_id, embedding, hash)?;
+        }
+    }
+
+    if complete {
+        set_meta(&tx, "embedding_template", &template)?;
+    }
+    tx.commit()?;
+
+    progress.finish_with_message("Repositories embedded");
+    Ok(outdated.len())
+}
+
+// Regenerate vectors from the repos already in the database, optionally with another model
+fn reembed_repos(
+    usernames: &Option<Vec<String>>,
+    model: &Option<String>,
+    force: bool,
+) -> Result<()> {
+    let mut conn = init_db()?;
+
+    let spec = match model {
+        Some(name) => model_spec(parse_configured_model(name)?, stored_model(&conn)?)?,
+        None => configured_model(&conn)?,
+    };
+
+    // Vectors of different models can't share the table, so a switch covers every repo
+    let switching = stored_model(&conn)? != Some((spec.name.clone(), spec.dimension))
+        && count(&conn, "SELECT COUNT(*) FROM repo_vectors")? > 0;
+    if switching && usernames.is_some() {
+        return Err(anyhow!(
+            "Switching the embedding model to {} re-embeds every repository, run reembed without --user",
+            spec.name
+        ));
+    }
+
+    let repos = match usernames {
+        Some(usernames) => {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT r.json FROM repos r WHERE {}",
+                user_filter_sql(usernames)
+            ))?;
+            stmt.query_map(rusqlite::params_from_iter(usernames), repo_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        }
+        None => {
+            let mut stmt = conn.prepare("SELECT json FROM repos")?;
+            stmt.query_map([], repo_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        }
+    };
+
+    if repos.is_empty() {
+        println!("No repositories to embed.");
+        return Ok(());
+    }
+
+    let embedded = embed_stored_repos(
+        &mut conn,
+        &spec,
+        &repos,
+        switching,
+        usernames.is_none(),
+        force,
+    )?;
+    println!(
+        "Embedded {} repositories with {}, {} were up to date",
+        embedded,
+        spec.name,
+        repos.len() - embedded
+    );
+
+    if let Some(configured) = &settings().profile.embedding_model
+        && parse_configured_model(configured)
+            .and_then(|backend| backend.name())
+            .ok()
+            != Some(spec.name.clone())
+    {
+        println!(
+            "Note: the config sets embedding_model = \"{}\", update it to \"{}\" to search these vectors.",
+            configured, spec.name
+        );
+    }
+
+    Ok(())
+}
+
+// The fastembed model named on the command line, else the configured one
+fn packaged_model(model: &Option<String>) -> Result<EmbeddingModel> {
+    if let Some(name) = model {
+        return parse_fastembed_model(name);
+    }
+    if let Some(api) = configured_api()? {
+        return Err(anyhow!(
+            "The {} backend computes embeddings on a server, there is no model to download",
+            api.prefix()
+        ));
+    }
+
+    let spec = configured_model(&init_db()?)?;
+    match spec.backend {
+        Backend::FastEmbed(model) => Ok(model),
+        Backend::Local(path) => Err(anyhow!(
+            "The configured model is already loaded from {}",
+            path.display()
+        )),
+        Backend::Http { .. } => Err(anyhow!(
+            "{} is computed on a server, there is no model to download",
+            spec.name
+        )),
+    }
+}
+
+// Download a fastembed model so later commands work without network access
+fn prefetch_model(model: &Option<String>) -> Result<()> {
+    let model = packaged_model(model)?;
+    let name = Backend::FastEmbed(model.clone()).name()?;
+    load_fastembed_model(&model)?;
+    println!(
+        "{} is in the model cache at {}",
+        name,
+        model_cache_dir()?.display()
+    );
+    Ok(())
+}
+
+// Copy a fastembed model into a directory that embedding_model_path can point
+// to on machines without access to Hugging Face
+fn export_model(dir: &Path, model: &Option<String>) -> Result<()> {
+    let model = packaged_model(model)?;
+    let info = TextEmbedding::get_model_info(&model)
+        .map_err(|e| anyhow!("Failed to look up embedding model: {}", e))?;
+    if !info.additional_files.is_empty() {
+        return Err(anyhow!(
+            "{} stores its weights in several files and can't be loaded from a directory",
+            info.model_code
+        ));
+    }
+
+    // Downloads the model if needed and checks that it loads
+    load_fastembed_model(&model)?;
+
+    create_dir_all(dir)?;
+    let files = std::iter::once((info.model_file.as_str(), MODEL_FILE))
+        .chain(TOKENIZER_FILES.iter().map(|file| (*file, *file)));
+    for (source, target) in files {
+        let source = cached_model_file(&info.model_code, source)?;
+        std::fs::copy(&source, dir.join(target))
+            .with_context(|| format!("Failed to copy {}", source.display()))?;
+    }
+
+    // The manifest keeps the model's name, so its vectors stay compatible
+    // with databases embedded by the downloaded model
+    let manifest = ModelManifest {
+        name: Some(info.model_code.clone()),
+        dimension: Some(info.dim),
+        pooling: TextEmbedding::get_default_pooling_method(&model)
+            .map(|pooling| format!("{:?}", pooling).to_lowercase()),
+        quantization: Some(
+            format!("{:?}", TextEmbedding::get_quantization_mode(&model)).to_lowercase(),
+        ),
+    };
+    std::fs::write(
+        dir.join(MODEL_MANIFEST),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    println!("Exported {} to {}", info.model_code, dir.display());
+    println!(
+        "Copy the directory to the offline machine and set embedding_model_path to it in the config."
+    );
+    Ok(())
+}
+
+// Show, set or clear the personal note of a cached repo
+fn edit_note(full_name: &str, text: &[String], clear: bool) -> Result<()> {
+    let mut conn = init_db()?;
+
+    let repo = match conn.query_row(
+        &format!(
+            "SELECT r.json, {} FROM repos r WHERE LOWER(r.full_name) = LOWER(?)",
+            NOTES_SQL
+        ),
+        params![full_name],
+        repo_from_row,
+    ) {
+        Ok(repo) => repo,
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            return Err(anyhow!("Repository {} not found in cache", full_name));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let repo_id = u64_to_sqlite(repo.id, "repo.id")?;
+
+    if text.is_empty() && !clear {
+        match &repo.notes {
+            Some(notes) => println!("{}", notes),
+            None => println!("No note for {}", repo.full_name),
+        }
+        return Ok(());
+    }
+
+    if clear {
+        conn.execute("DELETE FROM repo_notes WHERE repo_id = ?", params![repo_id])?;
+        println!("Cleared the note of {}", repo.full_name);
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO repo_notes (repo_id, note) VALUES (?, ?)",
+            params![repo_id, text.join(" ")],
+        )?;
+        println!("Saved the note of {}", repo.full_name);
+    }
+
+    // Keep the vector in step with the note when the template embeds notes,
+    // unless every vector needs re-embedding with another model anyway
+    if semantic_enabled() && template_uses(&embedding_template()?, "notes") {
+        let spec = configured_model(&conn)?;
+        if stored_model(&conn)? == Some((spec.name.clone(), spec.dimension))
+            && count(&conn, "SELECT COUNT(*) FROM repo_vectors")? > 0
+        {
+            embed_stored_repos(&mut conn, &spec, &[repo], false, false, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Remove a user, their stars and every repo no other cached user refers to
+fn forget_user(username: &str, yes: bool) -> Result<()> {
+    let mut conn = init_db()?;
+
+    let cached: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)",
+        params![username],
+        |row| row.get(0),
+    )?;
+    if !cached {
+        return Err(anyhow!("User {} is not in the cache", username));
+    }
+
+    let (stars, exclusive): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(NOT EXISTS(
+            SELECT 1 FROM user_stars o WHERE o.repo_id = s.repo_id AND o.username != s.username
+        )), 0)
+        FROM user_stars s WHERE s.username = ?",
+        params![username],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    if !yes
+        && !confirm(&format!(
+            "Remove {} and their {} stars from the cache? {} repositories starred by no other cached user will be deleted.",
+            username, stars, exclusive
+        ))?
+    {
+        println!("Aborted, the cache was not changed.");
+        return Ok(());
+    }
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let stars = tx.execute(
+        "DELETE FROM user_stars WHERE username = ?",
+        params![username],
+    )?;
+    tx.execute("DELETE FROM users WHERE username = ?", params![username])?;
+    let repos = collect_garbage(&tx)?;
+    tx.commit()?;
+
+    println!(
+        "Removed {} from the cache: {} stars, {} repositories",
+        username, stars, repos
+    );
+
+    Ok(())
+}
+
+// Problems found in the database by `db check`
+#[derive(Debug, Default)]
+struct DbReport {
+    // Messages from PRAGMA integrity_check, empty when the file is sound
+    integrity_errors: Vec<String>,
+    // The full-text index doesn't match the repos table
+    fts_out_of_sync: bool,
+    // Vectors whose repo no longer exists
+    orphan_vectors: usize,
+    // READMEs, notes and vector hashes whose repo no longer exists, per table
+    orphan_details: Vec<(&'static str, usize)>,
+    // Repos without a vector, which semantic search can't find
+    missing_vectors: usize,
+    // Repos no cached user stars anymore
+    unstarred_repos: usize,
+    // Stars referring to a missing repo or user
+    dangling_stars: usize,
+    // Model of the stored vectors, if it differs from the configured model
+    model_mismatch: Option<String>,
+    // Vectors were made with another embedding template
+    stale_template: bool,
+    // Semantic search is off, so the vectors don't need to be complete or current
+    keyword_only: bool,
+}
+
+impl DbReport {
+    fn is_healthy(&self) -> bool {
+        self.integrity_errors.is_empty()
+            && !self.fts_out_of_sync
+            && self.orphan_vectors == 0
+            && self.orphan_details.iter().all(|(_, count)| *count == 0)
+            && (self.missing_vectors == 0 || self.keyword_only)
+            && self.unstarred_repos == 0
+            && self.dangling_stars == 0
+            && self.model_mismatch.is_none()
+            && !self.stale_template
+    }
+}
+
+fn count(conn: &Connection, sql: &str) -> Result<usize> {
+    let count: i64 = conn.query_row(sql, [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+const ORPHAN_VECTORS_SQL: &str =
+    "SELECT rowid FROM repo_vectors WHERE rowid NOT IN (SELECT id FROM repos)";
+const MISSING_VECTORS_SQL: &str =
+    "SELECT id FROM repos WHERE id NOT IN (SELECT rowid FROM repo_vectors)";
+// Tables holding details of a repo by repo_id, cleaned up with it by triggers
+const REPO_DETAIL_TABLES: &[&str] = &["repo_readmes", "repo_notes", "repo_vector_hashes"];
+
+fn orphan_details_sql(table: &str) -> String {
+    format!(
+        "SELECT repo_id FROM {} WHERE repo_id NOT IN (SELECT id FROM repos)",
+        table
+    )
+}
+
+const DANGLING_STARS_SQL: &str = "SELECT rowid FROM user_stars
+    WHERE repo_id NOT IN (SELECT id FROM repos) OR username NOT IN (SELECT username FROM users)";
+
+fn check_db(conn: &Connection) -> Result<DbReport> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let integrity_errors = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|message| message != "ok")
+        .collect();
+
+    // Compares the external content index against the repos table, failing with
+    // SQLITE_CORRUPT_VTAB when they differ
+    let fts_out_of_sync = conn
+        .execute(
+            "INSERT INTO repos_fts(repos_fts, rank) VALUES ('integrity-check', 1)",
+            [],
+        )
+        .is_err();
+
+    // Keyword-only mode doesn't use the vectors, so their model doesn't matter
+    let keyword_only = !semantic_enabled();
+    let model_mismatch = if keyword_only {
+        None
+    } else {
+        let spec = configured_model(conn)?;
+        match stored_model(conn)? {
+            Some((name, dimension)) if name == spec.name && dimension == spec.dimension => None,
+            stored => Some(
+                stored
+                    .map(|(name, _)| name)
+                    .unwrap_or_else(|| "an unknown model".to_string()),
+            ),
+        }
+    };
+
+    Ok(DbReport {
+        integrity_errors,
+        fts_out_of_sync,
+        model_mismatch,
+        stale_template: !keyword_only && template_is_stale(conn)?,
+        keyword_only,
+        orphan_vectors: count(
+            conn,
+            &format!("SELECT COUNT(*) FROM ({})", ORPHAN_VECTORS_SQL),
+        )?,
+        orphan_details: REPO_DETAIL_TABLES
+            .iter()
+            .map(|table| {
+                let sql = format!("SELECT COUNT(*) FROM ({})", orphan_details_sql(table));
+                Ok((*table, count(conn, &sql)?))
+            })
+            .collect::<Result<_>>()?,
+        missing_vectors: count(
+            conn,
+            &format!("SELECT COUNT(*) FROM ({})", MISSING_VECTORS_SQL),
+        )?,
+        unstarred_repos: count(
+            conn,
+            "SELECT COUNT(*) FROM repos WHERE id NOT IN (SELECT repo_id FROM user_stars)",
+        )?,
+        dangling_stars: count(
+            conn,
+            &format!("SELECT COUNT(*) FROM ({})", DANGLING_STARS_SQL),
+        )?,
+    })
+}
+
+fn display_db_report(report: &DbReport) {
+    if report.integrity_errors.is_empty() {
+        println!("Integrity check: ok");
+    } else {
+        println!(
+            "Integrity check: {} problem(s)",
+            report.integrity_errors.len()
+        );
+        for message in &report.integrity_errors {
+            println!("  {}", message);
+        }
+    }
+
+    println!(
+        "Full-text index: {}",
+        if report.fts_out_of_sync {
+            "out of sync"
+        } else {
+            "ok"
+        }
+    );
+    println!("Orphan vectors: {}", report.orphan_vectors);
+    for (table, count) in &report.orphan_details {
+        println!("Orphan rows in {}: {}", table, count);
+    }
+    println!(
+        "Repos without a vector: {}{}",
+        report.missing_vectors,
+        if report.keyword_only && report.missing_vectors > 0 {
+            " (not needed in keyword-only mode)"
+        } else {
+            ""
+        }
+    );
+    println!("Repos no user stars: {}", report.unstarred_repos);
+    println!("Dangling stars: {}", report.dangling_stars);
+    if let Some(stored) = &report.model_mismatch {
+        println!(
+            "Embedding model: vectors made with {}, not the configured model",
+            stored
+        );
+    }
+    if report.stale_template {
+        println!("Embedding template: vectors made with a different template");
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Run the integrity checks and report what `db repair` would fix
+fn db_check() -> Result<()> {
+    let conn = init_db()?;
+    let report = check_db(&conn)?;
+    display_db_report(&report);
+
+    if !report.is_healthy() {
+        return Err(anyhow!(
+            "Database has problems. Run 'gh-stars db repair' to fix them."
+        ));
+    }
+
+    println!("Database is healthy.");
+    Ok(())
+}
+
+// Remove orphaned rows, rebuild the full-text index and embed repos missing a vector
+fn db_repair() -> Result<()> {
+    let mut conn = init_db()?;
+    let report = check_db(&conn)?;
+    display_db_report(&report);
+
+    if !report.integrity_errors.is_empty() {
+        return Err(anyhow!(
+            "The database file is damaged and can't be repaired in place. Delete it and fetch your stars again."
+        ));
+    }
+
+    if report.is_healthy() {
+        println!("Database is healthy, nothing to repair.");
+        return Ok(());
+    }
+
+    let spec = if report.keyword_only {
+        None
+    } else {
+        Some(configured_model(&conn)?)
+    };
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let dangling_stars = tx.execute(
+        &format!(
+            "DELETE FROM user_stars WHERE rowid IN ({})",
+            DANGLING_STARS_SQL
+        ),
+        [],
+    )?;
+    let unstarred_repos = collect_garbage(&tx)?;
+    let orphan_vectors = tx.execute(
+        &format!(
+            "DELETE FROM repo_vectors WHERE rowid IN ({})",
+            ORPHAN_VECTORS_SQL
+        ),
+        [],
+    )?;
+    let mut orphan_details = 0;
+    for table in REPO_DETAIL_TABLES {
+        orphan_details += tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE repo_id IN ({})",
+                table,
+                orphan_details_sql(table)
+            ),
+            [],
+        )?;
+    }
+
+    if report.fts_out_of_sync {
+        tx.execute("INSERT INTO repos_fts(repos_fts) VALUES ('rebuild')", [])?;
+        println!("Rebuilt the full-text index");
+    }
+
+    // Vectors of another model are useless for queries, so every repo gets re-embedded
+    if let Some(stored) = &report.model_mismatch
+        && let Some(spec) = &spec
+    {
+        reset_vectors(&tx, spec)?;
+        println!(
+            "Dropped the vectors made with {} to re-embed with {}",
+            stored, spec.name
+        );
+    }
+
+    // Keep the cleanup even if embedding fails below
+    tx.commit()?;
+    println!(
+        "Removed {} dangling star(s), {} unstarred repo(s), {} orphan vector(s) and {} orphan README, note or hash row(s)",
+        dangling_stars, unstarred_repos, orphan_vectors, orphan_details
+    );
 
-        // Generate embedding
-        let embedding = embedder
-            .embed(vec![embed_text], None)
-            .map_err(|e| anyhow!("Embedding failed: {}", e))?;
+    // Keyword-only mode leaves missing vectors to a later reembed
+    let Some(spec) = spec else {
+        return Ok(());
+    };
 
-        // Convert f32 vector to bytes for SQLite (safe version)
-        let embedding_bytes: Vec<u8> = embedding[0].iter().flat_map(|&f| f.to_le_bytes()).collect();
+    // A changed template makes every vector stale, otherwise only missing ones are embedded
+    let repos = {
+        let sql = if report.stale_template {
+            "SELECT json FROM repos".to_string()
+        } else {
+            format!(
+                "SELECT json FROM repos WHERE id IN ({})",
+                MISSING_VECTORS_SQL
+            )
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        stmt.query_map([], repo_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+    };
 
-        // Insert embedding
-        tx.execute(
-            "INSERT INTO repo_vectors(rowid, embedding) VALUES (?, ?)",
-            params![u64_to_sqlite(repo.id, "repo.id")?, embedding_bytes],
+    if !repos.is_empty() {
+        let embedded = embed_stored_repos(
+            &mut conn,
+            &spec,
+            &repos,
+            false,
+            report.stale_template,
+            false,
         )?;
+        println!("Embedded {} repo(s)", embedded);
     }
 
-    tx.commit()?;
-    progress.finish_with_message("Repositories stored in database");
-
     Ok(())
 }
 
-// Combined search function that uses both semantic and keyword search
-fn search_repos(
-    username: &str,
-    languages: &Option<Vec<String>>,
-    query: &str,
-    limit: usize,
-) -> Result<Vec<StarredRepo>> {
+// Rebuild the database file to reclaim the space left by deleted rows
+fn db_vacuum() -> Result<()> {
+    let db_path = get_db_path()?;
     let conn = init_db()?;
 
-    // If query is empty, just list repos with language filter
-    if query.is_empty() {
-        let mut sql = "SELECT json FROM repos WHERE username = ?".to_string();
-        let mut params: Vec<&dyn rusqlite::ToSql> = vec![&username as &dyn rusqlite::ToSql];
+    // Move pending pages from the WAL into the database file before measuring it
+    let checkpoint = || conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
 
-        // Add language filter if needed
-        if let Some(langs) = languages {
-            if !langs.is_empty() {
-                let placeholders: Vec<String> = (0..langs.len()).map(|_| "?".to_string()).collect();
-                sql.push_str(&format!(" AND language IN ({})", placeholders.join(",")));
+    checkpoint()?;
+    let before = std::fs::metadata(&db_path)?.len();
 
-                for lang in langs {
-                    params.push(lang as &dyn rusqlite::ToSql);
-                }
-            }
-        }
-
-        sql.push_str(&format!(" ORDER BY stars DESC LIMIT {}", limit));
+    conn.execute("VACUUM", [])?;
+    checkpoint()?;
 
-        let mut stmt = conn.prepare(&sql)?;
+    let after = std::fs::metadata(&db_path)?.len();
+    println!(
+        "Vacuumed {}: {} -> {} (reclaimed {})",
+        db_path.display(),
+        format_size(before),
+        format_size(after),
+        format_size(before.saturating_sub(after))
+    );
 
-        let repos_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-            let json: String = row.get(0)?;
-            let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    0,
-                    rusqlite::types::Type::Text,
-                    Box::new(e),
-                )
-            })?;
-            Ok(repo)
-        })?;
+    Ok(())
+}
 
-        let mut repos = Vec::new();
-        for repo in repos_iter {
-            repos.push(repo?);
-        }
+// Rebuild repo_vectors with another storage from the full precision vectors
+fn db_quantize(storage: VectorStorage) -> Result<()> {
+    let mut conn = init_db()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-        return Ok(repos);
+    let current = vector_storage(&tx)?;
+    if current == storage {
+        println!("Vectors are already stored as {}.", storage.name());
+        return Ok(());
     }
 
-    // Prepare language filter if needed
-    let language_filter = match languages {
-        Some(langs) if !langs.is_empty() => {
-            let placeholders: Vec<String> = (0..langs.len()).map(|_| "?".to_string()).collect();
-            format!(" AND r.language IN ({})", placeholders.join(","))
+    // Without a stored model the table is sized on the next fetch
+    let vectors = full_vectors(&tx, "repo_vectors", current)?;
+    if let Some((_, dimension)) = stored_model(&tx)? {
+        create_vector_table(&tx, "repo_vectors", storage, dimension)?;
+        for (repo_id, vector) in &vectors {
+            insert_vector(&tx, "repo_vectors", storage, *repo_id, vector)?;
         }
-        _ => String::new(),
-    };
-
-    // Format query for LIKE operations
-    let query_lower = format!("%{}%", query.to_lowercase());
-
-    // 1. Keyword search
-    let keyword_sql = format!(
-        "SELECT r.*, 1 AS search_type,
-        (CASE
-            WHEN LOWER(r.name) LIKE ? THEN 3
-            WHEN LOWER(r.full_name) LIKE ? THEN 2
-            WHEN LOWER(r.description) LIKE ? THEN 1
-            ELSE 0
-        END) AS score
-        FROM repos r
-        WHERE r.username = ?{}
-        AND (LOWER(r.name) LIKE ? OR LOWER(r.full_name) LIKE ? OR LOWER(r.description) LIKE ?)
-        ORDER BY score DESC, r.stars DESC
-        LIMIT {}",
-        language_filter, limit
-    );
+        println!(
+            "Stored {} vectors as {} (searched index {} -> {} bytes, stored {} -> {} bytes per vector).",
+            vectors.len(),
+            storage.name(),
+            current.index_bytes(dimension),
+            storage.index_bytes(dimension),
+            current.bytes_per_vector(dimension),
+            storage.bytes_per_vector(dimension)
+        );
+    } else {
+        println!("New vectors will be stored as {}.", storage.name());
+    }
+    set_meta(&tx, "vector_storage", storage.name())?;
+    tx.commit()?;
 
-    // Build parameters for query using vec macro
-    let mut keyword_params: Vec<&dyn rusqlite::ToSql> = vec![
-        // Add the LIKE parameters
-        &query_lower as &dyn rusqlite::ToSql,
-        &query_lower as &dyn rusqlite::ToSql,
-        &query_lower as &dyn rusqlite::ToSql,
-        // Add username
-        &username as &dyn rusqlite::ToSql,
-    ];
-
-    // Add language parameters
-    if let Some(langs) = languages {
-        for lang in langs {
-            keyword_params.push(lang as &dyn rusqlite::ToSql);
-        }
+    if !vectors.is_empty() {
+        println!("Run 'gh-stars db vacuum' to reclaim the space of the old vectors.");
     }
 
-    // Add the trailing LIKE params for the OR conditions
-    keyword_params.push(&query_lower as &dyn rusqlite::ToSql);
-    keyword_params.push(&query_lower as &dyn rusqlite::ToSql);
-    keyword_params.push(&query_lower as &dyn rusqlite::ToSql);
+    Ok(())
+}
 
-    // Execute keyword search
-    let mut keyword_stmt = conn.prepare(&keyword_sql)?;
+// Search every storage with stored vectors as queries, comparing the
+// results with exact float search
+fn db_benchmark(queries: usize, limit: usize) -> Result<()> {
+    let conn = init_db()?;
+    let vectors = full_vectors(&conn, "repo_vectors", vector_storage(&conn)?)?;
+    if vectors.len() < 2 || queries == 0 || limit == 0 {
+        return Err(anyhow!(
+            "Not enough vectors to benchmark, run 'gh-stars fetch' first"
+        ));
+    }
+    let dimension = vectors[0].1.len() / 4;
 
-    let mut results = Vec::new();
-    let mut seen_ids = std::collections::HashSet::new();
+    // Spread the queries over the stored vectors and leave each query's own
+    // repo out of its results
+    let step = (vectors.len() / queries).max(1);
+    let samples: Vec<_> = vectors.iter().step_by(step).take(queries).collect();
 
-    // Add keyword search results
-    let keyword_rows =
-        keyword_stmt.query_map(rusqlite::params_from_iter(keyword_params.iter()), |row| {
-            let json: String = row.get("json")?;
-            let score: i32 = row.get("score")?;
-            let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    0,
-                    rusqlite::types::Type::Text,
-                    Box::new(e),
-                )
-            })?;
-            Ok((repo, 0, score)) // 0 = keyword search
-        })?;
+    println!(
+        "Benchmarking {} queries against {} vectors ({} dimensions), top {} results",
+        samples.len(),
+        vectors.len(),
+        dimension,
+        limit
+    );
+    println!(
+        "Quantized search reranks the top {} candidates\n",
+        limit * RERANK_FACTOR
+    );
+    println!(
+        "{:<8} {:>12} {:>12} {:>14} {:>10}",
+        "Storage", "Index bytes", "Total bytes", "Latency/query", "Recall"
+    );
 
-    for row_result in keyword_rows {
-        let (repo, search_type, score) = row_result?;
-        if !seen_ids.contains(&repo.id) {
-            seen_ids.insert(repo.id);
-            results.push((repo, search_type, score));
+    let mut exact: Vec<Vec<i64>> = Vec::new();
+    for storage in VectorStorage::ALL {
+        if let Err(e) = storage.check_dimension(dimension) {
+            println!("{:<8} skipped: {}", storage.name(), e);
+            continue;
         }
-    }
-
-    // 2. Vector search if query isn't too short
-    if query.len() >= 3 {
-        // Initialize the embedder with the same cache dir as the database
-        let cache_dir = get_cache_dir()?;
-
-        let mut embedder = TextEmbedding::try_new(
-            InitOptions::new(EmbeddingModel::AllMiniLML6V2)
-                .with_show_download_progress(true)
-                .with_cache_dir(cache_dir),
-        )
-        .map_err(|e| anyhow!("Failed to initialize embedder: {}", e))?;
 
-        // Generate embedding for the query
-        let query_embedding = embedder
-            .embed(vec![query.to_string()], None)
-            .map_err(|e| anyhow!("Embedding query failed: {}", e))?;
+        let table = format!("temp.benchmark_{}", storage.name());
+        create_vector_table(&conn, &table, storage, dimension)?;
+        let tx = conn.unchecked_transaction()?;
+        for (repo_id, vector) in &vectors {
+            insert_vector(&tx, &table, storage, *repo_id, vector)?;
+        }
+        tx.commit()?;
+
+        let mut stmt = conn.prepare(&knn_sql(&table, storage, limit + 1, None))?;
+        let started = Instant::now();
+        let mut results = Vec::new();
+        for (repo_id, vector) in &samples {
+            let ids = stmt
+                .query_map(params![vector], |row| row.get::<_, i64>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            results.push(
+                ids.into_iter()
+                    .filter(|id| id != repo_id)
+                    .take(limit)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        let latency = started.elapsed() / samples.len() as u32;
+        drop(stmt);
+        conn.execute_batch(&format!("DROP TABLE {table}"))?;
 
-        // Convert f32 vector to bytes for SQLite (safe version)
-        let query_embedding_bytes: Vec<u8> = query_embedding[0]
+        // Float search is exact, so it is the reference for the others
+        if storage == VectorStorage::Float {
+            exact = results.clone();
+        }
+        let expected: usize = exact.iter().map(Vec::len).sum();
+        let found: usize = results
             .iter()
-            .flat_map(|&f| f.to_le_bytes())
-            .collect();
+            .zip(&exact)
+            .map(|(ids, exact)| ids.iter().filter(|id| exact.contains(id)).count())
+            .sum();
 
-        // Build the vector search query
-        let vector_sql = format!(
-            "SELECT r.*, 2 AS search_type, v.distance AS score
-            FROM repos r
-            JOIN (
-                SELECT rowid, distance
-                FROM repo_vectors
-                WHERE embedding MATCH ?
-                ORDER BY distance
-                LIMIT {}
-            ) v ON r.id = v.rowid
-            WHERE r.username = ?{}
-            ORDER BY v.distance ASC",
-            limit, language_filter
+        println!(
+            "{:<8} {:>12} {:>12} {:>11.2} ms {:>9.1}%",
+            storage.name(),
+            storage.index_bytes(dimension),
+            storage.bytes_per_vector(dimension),
+            latency.as_secs_f64() * 1000.0,
+            found as f64 * 100.0 / expected.max(1) as f64
         );
+    }
 
-        // Build vector search parameters without cloning
-        let mut vector_params: Vec<&dyn rusqlite::ToSql> = Vec::new();
+    Ok(())
+}
 
-        // Add embedding parameter
-        vector_params.push(&query_embedding_bytes as &dyn rusqlite::ToSql);
+#[derive(Debug, Serialize)]
+struct UserStats {
+    username: String,
+    stars: usize,
+    last_updated: i64,
+}
 
-        // Add username
-        vector_params.push(&username as &dyn rusqlite::ToSql);
+#[derive(Debug, Serialize)]
+struct CountStats {
+    name: String,
+    repos: usize,
+}
 
-        // Add language parameters
-        if let Some(langs) = languages {
-            for lang in langs {
-                vector_params.push(lang as &dyn rusqlite::ToSql);
-            }
-        }
+#[derive(Debug, Serialize)]
+struct CacheStats {
+    db_path: PathBuf,
+    db_size: u64,
+    schema_version: usize,
+    // Model of the stored vectors
+    embedding_model: Option<String>,
+    embedding_dimension: Option<usize>,
+    // Model used for new vectors and queries, None in keyword-only mode
+    configured_model: Option<String>,
+    // Vectors were made with another embedding template
+    stale_template: bool,
+    users: Vec<UserStats>,
+    repos: usize,
+    vectors: usize,
+    vector_storage: String,
+    cached_queries: usize,
+    top_languages: Vec<CountStats>,
+    top_owners: Vec<CountStats>,
+}
 
-        // Execute vector search
-        let mut vector_stmt = conn.prepare(&vector_sql)?;
-
-        let vector_rows =
-            vector_stmt.query_map(rusqlite::params_from_iter(vector_params.iter()), |row| {
-                let json: String = row.get("json")?;
-                let score: f64 = row.get("score")?;
-                let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        0,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                // Convert distance score to an integer for ranking
-                let int_score = ((1.0 - score) * 100.0) as i32;
-                Ok((repo, 1, int_score)) // 1 = vector search
-            })?;
+// Count repos per value of a column, most common first
+fn top_counts(conn: &Connection, column: &str, limit: usize) -> Result<Vec<CountStats>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {column}, COUNT(*) AS repos FROM repos
+        WHERE {column} IS NOT NULL
+        GROUP BY {column}
+        ORDER BY repos DESC, {column}
+        LIMIT ?"
+    ))?;
+    let counts = stmt
+        .query_map(params![limit as i64], |row| {
+            Ok(CountStats {
+                name: row.get(0)?,
+                repos: row.get::<_, i64>(1)? as usize,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(counts)
+}
 
-        // Add vector search results
-        for row_result in vector_rows {
-            let (repo, search_type, score) = row_result?;
-            if !seen_ids.contains(&repo.id) {
-                seen_ids.insert(repo.id);
-                results.push((repo, search_type, score));
-            }
+// Size of the database including its WAL files, which hold recent writes
+// until they are checkpointed into the main file
+fn db_files_size(db_path: &Path) -> Result<u64> {
+    let mut size = std::fs::metadata(db_path)?.len();
+    for suffix in ["-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            size += metadata.len();
         }
     }
+    Ok(size)
+}
 
-    // Sort by score descending
-    results.sort_by(|a, b| {
-        // First compare by search type (keyword first, then vector)
-        let type_compare = a.1.cmp(&b.1);
-        if type_compare != std::cmp::Ordering::Equal {
-            return type_compare;
-        }
+fn collect_stats(top: usize) -> Result<CacheStats> {
+    let db_path = get_db_path()?;
+    let conn = init_db()?;
 
-        // Then by score
-        let score_compare = b.2.cmp(&a.2);
-        if score_compare != std::cmp::Ordering::Equal {
-            return score_compare;
-        }
+    let mut stmt = conn.prepare(
+        "SELECT u.username, COUNT(s.repo_id), u.last_updated
+        FROM users u LEFT JOIN user_stars s ON s.username = u.username
+        GROUP BY u.username
+        ORDER BY u.username",
+    )?;
+    let users = stmt
+        .query_map([], |row| {
+            Ok(UserStats {
+                username: row.get(0)?,
+                stars: row.get::<_, i64>(1)? as usize,
+                last_updated: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Keyword-only mode has no model to compare with the stored vectors
+    let configured_model = if semantic_enabled() {
+        Some(configured_model(&conn)?.name)
+    } else {
+        None
+    };
+    let (stored_name, stored_dimension) = stored_model(&conn)?.unzip();
+
+    Ok(CacheStats {
+        db_size: db_files_size(&db_path)?,
+        db_path,
+        schema_version: schema_version(&conn)?,
+        embedding_model: stored_name,
+        embedding_dimension: stored_dimension,
+        stale_template: configured_model.is_some() && template_is_stale(&conn)?,
+        configured_model,
+        users,
+        repos: count(&conn, "SELECT COUNT(*) FROM repos")?,
+        vectors: count(&conn, "SELECT COUNT(*) FROM repo_vectors")?,
+        vector_storage: vector_storage(&conn)?.name().to_string(),
+        cached_queries: count(&conn, "SELECT COUNT(*) FROM query_embeddings")?,
+        top_languages: top_counts(&conn, "language", top)?,
+        top_owners: top_counts(&conn, "owner", top)?,
+    })
+}
 
-        // Finally by stars
-        b.0.stargazers_count.cmp(&a.0.stargazers_count)
-    });
+// Describe how long ago a unix timestamp was, e.g. "3 days ago"
+fn format_age(timestamp: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(timestamp);
+    let seconds = (now - timestamp).max(0);
+
+    let (value, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        value,
+        unit,
+        if value == 1 { "" } else { "s" }
+    )
+}
 
-    // Limit results to the requested amount
-    let results = results
-        .into_iter()
-        .take(limit)
-        .map(|(repo, _, _)| repo)
-        .collect();
+fn display_stats(stats: &CacheStats) {
+    println!("Database: {}", stats.db_path.display());
+    println!("Size: {}", format_size(stats.db_size));
+    println!("Schema version: {}", stats.schema_version);
+    if let (Some(model), Some(dimension)) = (&stats.embedding_model, stats.embedding_dimension) {
+        println!("Embedding model: {} ({} dimensions)", model, dimension);
+    }
+    match &stats.configured_model {
+        None => println!("Semantic search: off (keyword-only mode)"),
+        Some(configured) if stats.embedding_model.as_ref() != Some(configured) => println!(
+            "Configured model: {} (run 'gh-stars reembed' to switch)",
+            configured
+        ),
+        Some(_) => {}
+    }
+    if stats.stale_template {
+        println!("Embedding template: changed since embedding (run 'gh-stars reembed' to update)");
+    }
+    println!("Repositories: {}", stats.repos);
+    println!("Vectors: {} of {} repositories", stats.vectors, stats.repos);
+    println!("Vector storage: {}", stats.vector_storage);
+    println!("Cached query embeddings: {}", stats.cached_queries);
+
+    println!("\nUsers:");
+    if stats.users.is_empty() {
+        println!("  No cached users.");
+    }
+    for user in &stats.users {
+        println!(
+            "  {:<30} {:>6} stars  updated {}",
+            user.username,
+            user.stars,
+            format_age(user.last_updated)
+        );
+    }
 
-    Ok(results)
+    println!("\nTop languages:");
+    for language in &stats.top_languages {
+        println!("  {:<30} {:>6}", language.name, language.repos);
+    }
+
+    println!("\nTop owners:");
+    for owner in &stats.top_owners {
+        println!("  {:<30} {:>6}", owner.name, owner.repos);
+    }
 }
 
 fn display_repos(repos: &[StarredRepo]) {
@@ -710,18 +5191,19 @@ fn display_repos(repos: &[StarredRepo]) {
 
     println!("Found {} repositories:", repos.len());
     println!(
-        "{:<4} {:<60} {:<15} {:<8}",
-        "No.", "Repository", "Language", "Stars"
+        "{:<4} {:<60} {:<15} {:<8} {:<20}",
+        "No.", "Repository", "Language", "Stars", "Starred By"
     );
-    println!("{}", "-".repeat(100));
+    println!("{}", "-".repeat(110));
 
     for (i, repo) in repos.iter().enumerate() {
         println!(
-            "{:<4} {:<60} {:<15} {:<8}",
+            "{:<4} {:<60} {:<15} {:<8} {:<20}",
             i + 1,
             repo.full_name,
             repo.language.as_deref().unwrap_or("N/A"),
-            repo.stargazers_count
+            repo.stargazers_count,
+            repo.starred_by.join(", ")
         );
     }
 
@@ -736,6 +5218,10 @@ fn display_repo_info(repo: &StarredRepo) {
         println!("Description: {}", desc);
     }
 
+    if let Some(homepage) = repo.homepage.as_deref().filter(|h| !h.is_empty()) {
+        println!("Homepage: {}", homepage);
+    }
+
     println!("Owner: {}", repo.owner.login);
     println!("Language: {}", repo.language.as_deref().unwrap_or("N/A"));
     println!("Stars: {}", repo.stargazers_count);
@@ -748,15 +5234,27 @@ fn display_repo_info(repo: &StarredRepo) {
         println!("Open Issues: {}", issues);
     }
 
+    if !repo.topics.is_empty() {
+        println!("Topics: {}", repo.topics.join(", "));
+    }
+
     if let Some(created) = &repo.created_at {
         println!("Created: {}", created);
     }
 
     println!("Last Updated: {}", repo.updated_at);
+
+    if !repo.starred_by.is_empty() {
+        println!("Starred By: {}", repo.starred_by.join(", "));
+    }
+
+    if let Some(notes) = &repo.notes {
+        println!("Notes: {}", notes);
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+// Load sqlite-vec into every connection opened afterwards
+fn register_sqlite_vec() {
     unsafe {
         sqlite3_auto_extension(Some(std::mem::transmute::<
             *const (),
@@ -767,9 +5265,16 @@ async fn main() -> Result<()> {
             ) -> i32,
         >(sqlite3_vec_init as *const ())));
     }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    register_sqlite_vec();
 
     let cli = Cli::parse();
 
+    init_settings(cli.db.clone(), cli.profile.clone(), cli.no_embed)?;
+
     match &cli.command {
         Commands::Fetch {
             username,
@@ -783,14 +5288,21 @@ async fn main() -> Result<()> {
             language,
             terms,
             limit,
+            mode,
+            semantic_weight,
         } => {
-            // Join all search terms into a single query string, or use empty string if no terms provided
-            let query = if terms.is_empty() {
-                String::new()
-            } else {
-                terms.join(" ")
-            };
-            let usernames = match username {
+            // Join all search terms into a single query and split off its qualifiers
+            let query = SearchQuery::parse(&terms.join(" "))?;
+            // user: qualifiers win, then --username, the profile's users and every cached user
+            let limit = limit.or(settings().profile.limit).unwrap_or(30);
+            let mode = configured_search_mode(*mode)?;
+            let semantic_weight = configured_semantic_weight(*semantic_weight)?;
+            let qualified_users = (!query.users.is_empty()).then(|| query.users.clone());
+            let usernames = match qualified_users
+                .as_ref()
+                .or(username.as_ref())
+                .or(settings().profile.users.as_ref())
+            {
                 Some(users) => users.clone(),
                 None => {
                     // If no username is provided, get all cached users
@@ -817,20 +5329,14 @@ async fn main() -> Result<()> {
                 limit
             );
 
-            let mut all_results = Vec::new();
-            for username in &usernames {
-                let results = search_repos(username, language, &query, *limit)?;
-                all_results.extend(results);
-            }
-
-            // Sort by stars and limit to the requested number
-            all_results.sort_by(|a, b| b.stargazers_count.cmp(&a.stargazers_count));
-            let limited_results = all_results.into_iter().take(*limit).collect::<Vec<_>>();
+            let results = search_repos(&usernames, language, &query, limit, mode, semantic_weight)?;
 
-            display_repos(&limited_results);
+            display_repos(&results);
         }
         Commands::List { username, limit } => {
-            let usernames = match username {
+            // Fall back to the profile's users, then to every cached user
+            let limit = limit.or(settings().profile.limit).unwrap_or(30);
+            let usernames = match username.as_ref().or(settings().profile.users.as_ref()) {
                 Some(users) => users.clone(),
                 None => {
                     // If no username is provided, get all cached users
@@ -857,18 +5363,17 @@ async fn main() -> Result<()> {
                 limit
             );
 
-            let mut all_results = Vec::new();
-            for username in &usernames {
-                // Use the search function with empty query to list repos
-                let results = search_repos(username, &None, "", *limit)?;
-                all_results.extend(results);
-            }
-
-            // Sort by stars and limit to the requested number
-            all_results.sort_by(|a, b| b.stargazers_count.cmp(&a.stargazers_count));
-            let limited_results = all_results.into_iter().take(*limit).collect::<Vec<_>>();
-
-            display_repos(&limited_results);
+            // Use the search function with empty query to list repos
+            let results = search_repos(
+                &usernames,
+                &None,
+                &SearchQuery::default(),
+                limit,
+                SearchMode::Keyword,
+                0.0,
+            )?;
+
+            display_repos(&results);
         }
         Commands::Info { repo } => {
             // Parse the repo string in format "user/repo"
@@ -879,42 +5384,30 @@ async fn main() -> Result<()> {
                 ));
             }
 
-            let username = parts[0];
+            let owner = parts[0];
             let repo_name = parts[1];
 
             // Open database connection
             let conn = init_db()?;
 
             // Try to find the repository by full_name first (this is what's displayed in the list)
-            let query = "SELECT json FROM repos WHERE full_name = ?";
-            match conn.query_row(query, params![repo], |row| {
-                let json: String = row.get(0)?;
-                let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        0,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                Ok(repo)
-            }) {
+            let query = format!(
+                "SELECT r.json, {}, {} FROM repos r WHERE r.full_name = ?",
+                STARRED_BY_SQL, NOTES_SQL
+            );
+            match conn.query_row(&query, params![repo], repo_from_row) {
                 Ok(repo) => {
                     display_repo_info(&repo);
                 }
                 Err(rusqlite::Error::QueryReturnedNoRows) => {
-                    // If not found by full_name, try with username and name
-                    let fallback_query = "SELECT json FROM repos WHERE username = ? AND name = ?";
-                    match conn.query_row(fallback_query, params![username, repo_name], |row| {
-                        let json: String = row.get(0)?;
-                        let repo: StarredRepo = serde_json::from_str(&json).map_err(|e| {
-                            rusqlite::Error::FromSqlConversionFailure(
-                                0,
-                                rusqlite::types::Type::Text,
-                                Box::new(e),
-                            )
-                        })?;
-                        Ok(repo)
-                    }) {
+                    // If not found by full_name, try owner and name ignoring case
+                    let fallback_query = format!(
+                        "SELECT r.json, {}, {} FROM repos r
+                        WHERE LOWER(r.owner) = LOWER(?) AND LOWER(r.name) = LOWER(?)",
+                        STARRED_BY_SQL, NOTES_SQL
+                    );
+                    match conn.query_row(&fallback_query, params![owner, repo_name], repo_from_row)
+                    {
                         Ok(repo) => {
                             display_repo_info(&repo);
                         }
@@ -931,7 +5424,449 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Commands::Prune {
+            username,
+            archived,
+            stale_years,
+            forks,
+            language,
+            query,
+            dry_run,
+            yes,
+            token,
+        } => {
+            let rules = PruneRules {
+                archived: *archived,
+                stale_years: *stale_years,
+                forks: *forks,
+                languages: language.clone(),
+                query: query.clone(),
+            };
+            prune_stars(username, &rules, *dry_run, *yes, token).await?;
+        }
+        Commands::Restar { log, token } => {
+            restar_repos(log, token).await?;
+        }
+        Commands::Backup { file, compress } => {
+            backup_db(file, *compress)?;
+        }
+        Commands::Restore { file, yes } => {
+            restore_db(file, *yes)?;
+        }
+        Commands::Note { repo, text, clear } => {
+            edit_note(repo, text, *clear)?;
+        }
+        Commands::Reembed { user, model, force } => {
+            reembed_repos(user, model, *force)?;
+        }
+        Commands::Reindex => {
+            reindex_repos()?;
+        }
+        Commands::Forget { username, yes } => {
+            forget_user(username, *yes)?;
+        }
+        Commands::Stats { json, top } => {
+            let stats = collect_stats(*top)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                display_stats(&stats);
+            }
+        }
+        Commands::Db { command } => match command {
+            DbCommands::Check => db_check()?,
+            DbCommands::Repair => db_repair()?,
+            DbCommands::Vacuum => db_vacuum()?,
+            DbCommands::Quantize { storage } => db_quantize(*storage)?,
+            DbCommands::Benchmark { queries, limit } => db_benchmark(*queries, *limit)?,
+        },
+        Commands::Model { command } => match command {
+            ModelCommands::Prefetch { model } => prefetch_model(model)?,
+            ModelCommands::Export { dir, model } => export_model(dir, model)?,
+        },
+        Commands::Daemon { command } => daemon_command(command)?,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::types::Value;
+
+    fn parse(input: &str) -> SearchQuery {
+        SearchQuery::parse(input).unwrap()
+    }
+
+    fn error(input: &str) -> String {
+        SearchQuery::parse(input).unwrap_err().to_string()
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    const FTS_FILTER: &str = "r.id IN (SELECT rowid FROM repos_fts WHERE repos_fts MATCH ?)";
+    const FTS_EXCLUDE: &str = "r.id NOT IN (SELECT rowid FROM repos_fts WHERE repos_fts MATCH ?)";
+
+    #[test]
+    fn free_text_only() {
+        let query = parse("  async   runtime ");
+        assert_eq!(query.text, "async runtime");
+        assert!(query.filters.is_empty());
+        assert!(query.params.is_empty());
+        assert!(query.users.is_empty());
+    }
+
+    #[test]
+    fn language_qualifiers_match_any_value() {
+        let query = parse("lang:Rust web language:go,C");
+        assert_eq!(query.text, "web");
+        assert_eq!(query.filters, ["lower(r.language) IN (?,?,?)"]);
+        assert_eq!(query.params, [text("rust"), text("go"), text("c")]);
+    }
+
+    #[test]
+    fn owner_qualifier() {
+        let query = parse("owner:Tokio-RS");
+        assert_eq!(query.text, "");
+        assert_eq!(query.filters, ["lower(r.owner) IN (?)"]);
+        assert_eq!(query.params, [text("tokio-rs")]);
+    }
+
+    #[test]
+    fn topic_qualifiers_must_all_match() {
+        let query = parse("topic:async topic:Web");
+        assert_eq!(
+            query.filters,
+            [
+                "instr(' ' || lower(COALESCE(r.topics, '')) || ' ', ?) > 0",
+                "instr(' ' || lower(COALESCE(r.topics, '')) || ' ', ?) > 0",
+            ]
+        );
+        assert_eq!(query.params, [text(" async "), text(" web ")]);
+    }
+
+    #[test]
+    fn user_qualifiers_collect_users() {
+        let query = parse("user:alice,bob cli user:carol");
+        assert_eq!(query.users, ["alice", "bob", "carol"]);
+        assert_eq!(query.text, "cli");
+        assert!(query.filters.is_empty());
+    }
+
+    #[test]
+    fn archived_qualifiers() {
+        assert_eq!(parse("archived:true").filters, ["r.archived = 1"]);
+        assert_eq!(parse("archived:FALSE").filters, ["r.archived = 0"]);
+        assert_eq!(parse("-archived").filters, ["r.archived = 0"]);
+        assert_eq!(parse("-Archived").filters, ["r.archived = 0"]);
+    }
+
+    #[test]
+    fn star_comparisons() {
+        for (input, filter) in [
+            ("stars:500", "r.stars = ?"),
+            ("stars:>500", "r.stars > ?"),
+            ("stars:>=500", "r.stars >= ?"),
+            ("stars:<500", "r.stars < ?"),
+            ("stars:<=500", "r.stars <= ?"),
+        ] {
+            let query = parse(input);
+            assert_eq!(query.filters, [filter], "{}", input);
+            assert_eq!(query.params, [Value::Integer(500)], "{}", input);
+        }
+    }
+
+    #[test]
+    fn star_ranges() {
+        let query = parse("stars:10..100");
+        assert_eq!(query.filters, ["r.stars >= ?", "r.stars <= ?"]);
+        assert_eq!(query.params, [Value::Integer(10), Value::Integer(100)]);
+
+        let query = parse("stars:10..*");
+        assert_eq!(query.filters, ["r.stars >= ?"]);
+        assert_eq!(query.params, [Value::Integer(10)]);
+
+        let query = parse("stars:*..100");
+        assert_eq!(query.filters, ["r.stars <= ?"]);
+        assert_eq!(query.params, [Value::Integer(100)]);
+
+        assert!(parse("stars:*..*").filters.is_empty());
+    }
+
+    #[test]
+    fn pushed_dates() {
+        let query = parse("pushed:>2024-01-01");
+        assert_eq!(query.filters, ["date(r.pushed_at) > ?"]);
+        assert_eq!(query.params, [text("2024-01-01")]);
+
+        let query = parse("pushed:2024-01-01..2024-06-30");
+        assert_eq!(
+            query.filters,
+            ["date(r.pushed_at) >= ?", "date(r.pushed_at) <= ?"]
+        );
+        assert_eq!(query.params, [text("2024-01-01"), text("2024-06-30")]);
+
+        let query = parse("pushed:<=2023-12-31");
+        assert_eq!(query.filters, ["date(r.pushed_at) <= ?"]);
+    }
+
+    #[test]
+    fn negated_qualifiers() {
+        let query = parse("-lang:JavaScript,ts");
+        assert_eq!(
+            query.filters,
+            ["COALESCE(lower(r.language), '') NOT IN (?,?)"]
+        );
+        assert_eq!(query.params, [text("javascript"), text("ts")]);
+
+        let query = parse("-owner:microsoft");
+        assert_eq!(query.filters, ["COALESCE(lower(r.owner), '') NOT IN (?)"]);
+
+        let query = parse("-topic:deprecated");
+        assert_eq!(
+            query.filters,
+            ["instr(' ' || lower(COALESCE(r.topics, '')) || ' ', ?) = 0"]
+        );
+        assert_eq!(query.params, [text(" deprecated ")]);
+    }
+
+    #[test]
+    fn excluded_words() {
+        let query = parse("web -old -\"some phrase\"");
+        assert_eq!(query.text, "web");
+        assert_eq!(query.filters, [FTS_EXCLUDE, FTS_EXCLUDE]);
+        assert_eq!(query.params, [text("\"old\""), text("\"some phrase\"")]);
+    }
+
+    #[test]
+    fn quoted_phrases() {
+        let query = parse("\"exact phrase\" rust");
+        assert_eq!(query.text, "exact phrase rust");
+        assert_eq!(query.filters, [FTS_FILTER]);
+        assert_eq!(query.params, [text("\"exact phrase\"")]);
+
+        // A quoted qualifier is a phrase, not a filter
+        let query = parse("\"lang:rust\"");
+        assert_eq!(query.text, "lang:rust");
+        assert_eq!(query.filters, [FTS_FILTER]);
+        assert_eq!(query.params, [text("\"lang rust\"")]);
+    }
+
+    #[test]
+    fn quoted_qualifier_values() {
+        let query = parse("lang:\"Objective-C\" owner:\"tokio-rs\"");
+        assert_eq!(query.text, "");
+        assert_eq!(
+            query.filters,
+            ["lower(r.language) IN (?)", "lower(r.owner) IN (?)"]
+        );
+        assert_eq!(query.params, [text("objective-c"), text("tokio-rs")]);
+    }
+
+    #[test]
+    fn mixed_query() {
+        let query =
+            parse("async lang:rust stars:>500 owner:tokio-rs topic:async user:alice -archived");
+        assert_eq!(query.text, "async");
+        assert_eq!(query.users, ["alice"]);
+        assert_eq!(query.filters.len(), 5);
+        assert_eq!(
+            query.filter_sql(),
+            " AND r.stars > ? AND instr(' ' || lower(COALESCE(r.topics, '')) || ' ', ?) > 0 AND r.archived = 0 AND lower(r.language) IN (?) AND lower(r.owner) IN (?)"
+        );
+        assert_eq!(
+            query.params,
+            [
+                Value::Integer(500),
+                text(" async "),
+                text("rust"),
+                text("tokio-rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        assert_eq!(error("stars:abc"), "'abc' in stars:abc is not a number");
+        assert_eq!(error("stars:>ten"), "'ten' in stars:>ten is not a number");
+        assert_eq!(error("stars:10.."), "'' in stars:10.. is not a number");
+        assert_eq!(error("stars:1.5"), "'1.5' in stars:1.5 is not a number");
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(
+            error("pushed:>2024-13-01"),
+            "'2024-13-01' in pushed:>2024-13-01 is not a date like 2024-01-31"
+        );
+        assert_eq!(
+            error("pushed:2024-1-1"),
+            "'2024-1-1' in pushed:2024-1-1 is not a date like 2024-01-31"
+        );
+        assert_eq!(
+            error("pushed:yesterday..2024-01-01"),
+            "'yesterday' in pushed:yesterday..2024-01-01 is not a date like 2024-01-31"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_free_text() {
+        let query = parse("https://github.com/tokio-rs/axum foo:bar todo: lang:rust");
+        assert_eq!(query.text, "https://github.com/tokio-rs/axum foo:bar todo:");
+        assert_eq!(query.filters, ["lower(r.language) IN (?)"]);
+
+        let query = parse("-note:draft");
+        assert_eq!(query.text, "");
+        assert_eq!(query.filters, [FTS_EXCLUDE]);
+        assert_eq!(query.params, [text("\"note draft\"")]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(error("lang:"), "'lang:' needs a value");
+        assert_eq!(
+            error("archived:maybe"),
+            "archived: must be true or false, not 'maybe'"
+        );
+        assert_eq!(
+            error("rust \"unterminated"),
+            "Unterminated quote in 'rust \"unterminated'"
+        );
+        assert_eq!(error("rust -"), "'-' must be followed by a word to exclude");
+        assert_eq!(
+            error("-user:bob"),
+            "'-user:' isn't supported, only lang:, owner: and topic: can be negated"
+        );
+        assert_eq!(
+            error("-stars:>5"),
+            "'-stars:' isn't supported, only lang:, owner: and topic: can be negated"
+        );
+        assert_eq!(error("\"...\""), "'...' has no words to match");
+        assert_eq!(error("-\"!!\""), "'-!!' has no words to match");
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            query_tokens("a \"b c\" -d -\"e f\" k:\"g h\"").unwrap(),
+            [
+                ("a".to_string(), false, false),
+                ("b c".to_string(), true, false),
+                ("d".to_string(), false, true),
+                ("e f".to_string(), true, true),
+                ("k:g h".to_string(), false, false),
+            ]
+        );
+        assert!(query_tokens("\"\"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2024-02-29").as_deref(), Some("2024-02-29"));
+        assert_eq!(parse_date("2024-00-10"), None);
+        assert_eq!(parse_date("2024-01-32"), None);
+        assert_eq!(parse_date("24-01-01"), None);
+        assert_eq!(parse_date("2024/01/01"), None);
+        assert_eq!(parse_date("2024-01-01T00:00"), None);
+    }
+
+    fn repo(id: u64, stargazers_count: u64) -> StarredRepo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": format!("r{}", id),
+            "full_name": format!("o/r{}", id),
+            "owner": { "login": "o" },
+            "html_url": format!("https://github.com/o/r{}", id),
+            "description": null,
+            "language": null,
+            "stargazers_count": stargazers_count,
+            "forks_count": null,
+            "open_issues_count": null,
+            "updated_at": "2024-01-01T00:00:00Z",
+            "created_at": null,
+        }))
+        .unwrap()
+    }
+
+    fn ranked(repos: &[(u64, u64)]) -> Vec<(StarredRepo, f64)> {
+        repos
+            .iter()
+            .map(|&(id, stars)| (repo(id, stars), 0.0))
+            .collect()
+    }
+
+    fn ids(repos: &[StarredRepo]) -> Vec<u64> {
+        repos.iter().map(|repo| repo.id).collect()
+    }
+
+    #[test]
+    fn zero_weight_list_does_not_change_order() {
+        let keyword = [(1, 10), (2, 20), (3, 30)];
+        let semantic = [(3, 30), (2, 20), (4, 40)];
+        let fused = fuse_rankings(vec![(ranked(&keyword), 1.0), (ranked(&semantic), 0.0)]);
+        assert_eq!(ids(&fused), [1, 2, 3, 4]);
+
+        let fused = fuse_rankings(vec![(ranked(&semantic), 0.0), (ranked(&keyword), 1.0)]);
+        assert_eq!(ids(&fused)[..3], [1, 2, 3]);
+    }
+
+    #[test]
+    fn fused_ties_go_to_more_stars() {
+        // Each repo ranks first in one list and second in the other
+        let first = [(1, 10), (2, 20)];
+        let second = [(2, 20), (1, 10)];
+        let fused = fuse_rankings(vec![(ranked(&first), 0.5), (ranked(&second), 0.5)]);
+        assert_eq!(ids(&fused), [2, 1]);
+        let fused = fuse_rankings(vec![(ranked(&second), 0.5), (ranked(&first), 0.5)]);
+        assert_eq!(ids(&fused), [2, 1]);
+
+        // Everything only in a zero weight list ties at 0
+        let fused = fuse_rankings(vec![(ranked(&[(5, 1), (6, 3), (7, 2)]), 0.0)]);
+        assert_eq!(ids(&fused), [6, 7, 5]);
+    }
+
+    #[test]
+    fn migrate_baseline_database() {
+        register_sqlite_vec();
+        let dir = std::env::temp_dir().join(format!("gh-stars-migrate-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let db_path = dir.join("stars.db");
+        let mut conn = Connection::open(&db_path).unwrap();
+
+        // The schema from before versioning, with a repo starred by two users
+        conn.execute_batch(MIGRATIONS[0].sql).unwrap();
+        let json = |id: u32| serde_json::to_string(&repo(id.into(), 10)).unwrap();
+        conn.execute_batch("INSERT INTO users VALUES ('alice', 0), ('bob', 0)")
+            .unwrap();
+        for (id, username) in [(1u32, "alice"), (1, "bob"), (2, "alice")] {
+            conn.execute(
+                "INSERT INTO repos (id, username, full_name, name, owner, html_url, stars, updated_at, json)
+                VALUES (?1, ?2, 'o/r' || ?1, 'r' || ?1, 'o', '', ?1 * 10, '', ?3)",
+                params![id, username, json(id)],
+            )
+            .unwrap();
+        }
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        migrate(&mut conn, &db_path).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM repos").unwrap(), 2);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM user_stars").unwrap(), 3);
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM user_stars WHERE repo_id = 1").unwrap(),
+            2
+        );
+        assert!(dir.join("stars.v0.bak").exists());
+
+        // Migrating an up to date database is a no-op
+        migrate(&mut conn, &db_path).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len());
+
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}